@@ -0,0 +1,92 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  testers,
+  coreutils,
+  util-linux,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."leaver".process.argv = [
+      (lib.getExe (writeShellApplication {
+        name = "leaver";
+        runtimeInputs = [
+          coreutils
+          util-linux
+        ];
+        text = ''
+          # Escapes the process group, but not the cgroup of the service
+          (setsid sleep 1000 &> /dev/null & echo "$!" > daemon)
+          sleep 0.5
+        '';
+      }))
+    ];
+    services."daemonizing".process.argv = [
+      (lib.getExe (writeShellApplication {
+        name = "daemonizing";
+        runtimeInputs = [
+          coreutils
+          util-linux
+        ];
+        text = ''
+          # Orphans in sessions of their own, only attributable by their cgroup
+          for _ in $(seq 3); do
+            (setsid sleep 0.2 &> /dev/null &)
+          done
+          exec sleep 1000
+        '';
+      }))
+    ];
+    services."limited".process = {
+      argv = [
+        (lib.getExe (writeShellApplication {
+          name = "limited";
+          runtimeInputs = [ coreutils ];
+          text = ''
+            touch limited-started
+            exec sleep 1000
+          '';
+        }))
+      ];
+      memoryMax = "64M";
+      pidsMax = 16;
+    };
+    settings = {
+      cgroups.enable = true;
+      stopTimeout = 500;
+      restart.mode = "never";
+    };
+  };
+
+  cgroup = "/sys/fs/cgroup/system.slice/nimi-cgroups.service";
+in
+testers.runNixOSTest {
+  name = "cgroups-contain-services";
+  nodes.machine = { };
+  testScript = ''
+    start_all()
+    machine.wait_for_unit("multi-user.target")
+
+    machine.succeed("mkdir -p /tmp/cgroups")
+    machine.succeed(
+      "systemd-run --unit=nimi-cgroups -p Delegate=yes -p WorkingDirectory=/tmp/cgroups "
+      + "${lib.getExe nimiWrapper}"
+    )
+    machine.wait_until_succeeds("test -e /tmp/cgroups/limited-started")
+
+    machine.succeed("test \"$(cat ${cgroup}/limited.service/memory.max)\" = 67108864")
+    machine.succeed("test \"$(cat ${cgroup}/limited.service/pids.max)\" = 16")
+
+    # Once leaver exited, the daemon left in its cgroup is killed and the cgroup removed
+    machine.wait_until_succeeds("! test -e ${cgroup}/leaver.service")
+    machine.wait_until_fails("kill -0 \"$(cat /tmp/cgroups/daemon)\"")
+
+    machine.succeed("systemctl stop nimi-cgroups")
+    machine.succeed("journalctl -u nimi-cgroups --no-pager | grep -F \"Service used\"")
+    machine.succeed(
+      "journalctl -u nimi-cgroups --no-pager "
+      + "| grep -F \"daemonizing] Reaped 3 orphaned processes of the service\""
+    )
+  '';
+}
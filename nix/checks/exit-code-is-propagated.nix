@@ -0,0 +1,50 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  mkNimi =
+    name: text: exit:
+    lib.getExe (
+      nimi.mkNimiBin {
+        services.${name} = {
+          process.argv = [
+            (lib.getExe (writeShellApplication {
+              inherit name text;
+            }))
+          ];
+          critical = true;
+        };
+        settings = {
+          restart.mode = "never";
+          inherit exit;
+        };
+      }
+    );
+
+  exitsWithCode = mkNimi "exits-with-code" "exit 3" { };
+  killedBySignal = mkNimi "killed-by-signal" "kill -TERM $$" { };
+  remapped = mkNimi "remapped" "kill -TERM $$" { remapToSuccess = [ 143 ]; };
+in
+runCommandLocal "exit-code-is-propagated" { } ''
+  set -euo pipefail
+
+  assert_exit_code() {
+    status=0
+    timeout 60 "$2" &> nimi_logs.txt || status=$?
+    if [ "$status" != "$3" ]; then
+      echo "Expected nimi to exit with $3 when $1, got $status"
+      echo "nimi logs: $(cat nimi_logs.txt)"
+      exit 1
+    fi
+  }
+
+  assert_exit_code "a service exits with 3" ${exitsWithCode} 3
+  assert_exit_code "a service is killed by SIGTERM" ${killedBySignal} 143
+  assert_exit_code "143 is remapped to success" ${remapped} 0
+
+  echo "Successfully propagated the exit code of failed critical services"
+  mkdir "$out"
+''
@@ -0,0 +1,87 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  mkServer =
+    name:
+    lib.getExe (writeShellApplication {
+      inherit name;
+      text = ''
+        trap 'echo "${name}" >> stopped; exit 0' TERM
+        sleep 1000 &
+        wait
+      '';
+    });
+
+  mkExiting =
+    name: code:
+    lib.getExe (writeShellApplication {
+      inherit name;
+      text = ''
+        sleep 0.5
+        exit ${toString code}
+      '';
+    });
+
+  codeFrom = nimi.mkNimiBin {
+    services."db".process.argv = [ (mkServer "db") ];
+    services."tests".process.argv = [ (mkExiting "tests" 4) ];
+    settings = {
+      restart.mode = "never";
+      exit.codeFrom = "tests";
+    };
+  };
+
+  # tests never runs, as the db it requires fails
+  codeFromNeverRan = nimi.mkNimiBin {
+    services."db".process.argv = [ (mkExiting "db" 3) ];
+    services."tests" = {
+      process.argv = [ (mkExiting "tests" 0) ];
+      requires = [ "db" ];
+    };
+    settings = {
+      restart.mode = "never";
+      exit.codeFrom = "tests";
+    };
+  };
+
+  abortOnExit = nimi.mkNimiBin {
+    services."server".process.argv = [ (mkServer "server") ];
+    services."oneoff".process.argv = [ (mkExiting "oneoff" 0) ];
+    settings.exit.abortOnExit = true;
+  };
+in
+runCommandLocal "exit-policies-stop-services" { } ''
+  set -euo pipefail
+
+  status=0
+  timeout 60 ${lib.getExe codeFrom} &> nimi_logs.txt || status=$?
+  if [ "$status" != 4 ]; then
+    echo "Expected nimi to exit with the exit code of tests, got $status"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  status=0
+  timeout 60 ${lib.getExe codeFromNeverRan} &> nimi_logs.txt || status=$?
+  if [ "$status" != 1 ]; then
+    echo "Expected nimi to exit with 1 when tests failed without running, got $status"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  timeout 60 ${lib.getExe abortOnExit} &> nimi_logs.txt
+
+  stopped="$(tr '\n' ' ' < stopped)"
+  if [ "db server " != "$stopped" ]; then
+    echo "Expected db and server to be stopped, got: $stopped"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully stopped services once a service exited"
+  mkdir "$out"
+''
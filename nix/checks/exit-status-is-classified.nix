@@ -0,0 +1,67 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  mkExiting =
+    name: code:
+    lib.getExe (writeShellApplication {
+      inherit name;
+      text = ''
+        echo "${name} run"
+        exit ${toString code}
+      '';
+    });
+
+  nimiWrapper = nimi.mkNimiBin {
+    services."clean" = {
+      process.argv = [ (mkExiting "clean" 75) ];
+      successExitStatus.codes = [ 75 ];
+    };
+    services."prevented" = {
+      process.argv = [ (mkExiting "prevented" 78) ];
+      restartPreventExitStatus.codes = [ 78 ];
+    };
+    services."failing" = {
+      process.argv = [ (mkExiting "failing" 1) ];
+    };
+    settings.restart = {
+      mode = "up-to-count";
+      condition = "on-failure";
+      count = 2;
+      time = 100;
+    };
+  };
+in
+runCommandLocal "exit-status-is-classified" { } ''
+  set -euo pipefail
+
+  timeout 60 ${lib.getExe nimiWrapper} &> nimi_logs.txt
+
+  assert_runs() {
+    runs="$(grep -c "$1 run" nimi_logs.txt || true)"
+    if [ "$runs" != "$2" ]; then
+      echo "Expected $1 to run $2 times, got $runs"
+      echo "nimi logs: $(cat nimi_logs.txt)"
+      exit 1
+    fi
+  }
+
+  # Exit code 75 is clean, so on-failure doesn't restart it
+  assert_runs clean 1
+  # Exit code 78 prevents restarts
+  assert_runs prevented 1
+  # Exit code 1 is a failure, so it uses up both restarts
+  assert_runs failing 3
+
+  if grep -q "Service clean failed" nimi_logs.txt; then
+    echo "A successExitStatus exit was treated as a failure"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully classified exit statuses"
+  mkdir "$out"
+''
@@ -0,0 +1,77 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  mkHook =
+    name: text:
+    lib.getExe (writeShellApplication {
+      inherit name text;
+    });
+
+  nimiWrapper = nimi.mkNimiBin {
+    services."web" = {
+      process = {
+        argv = [
+          (mkHook "web" ''
+            sleep 1
+            exit 3
+          '')
+        ];
+        environment.GREETING = "hello";
+      };
+      hooks = {
+        preStart = [
+          {
+            argv = [
+              (mkHook "pre-start" ''
+                echo "preStart $GREETING" >> hooks
+              '')
+            ];
+          }
+        ];
+        # Outlives the service, which has to be noticed while it still runs
+        postStart = [
+          {
+            argv = [
+              (mkHook "post-start" ''
+                if [ -n "$XDG_CONFIG_HOME" ]; then
+                  echo "postStart $GREETING" >> hooks
+                fi
+                sleep 30
+                echo "postStart finished" >> hooks
+              '')
+            ];
+          }
+        ];
+        postStop = [
+          {
+            argv = [
+              (mkHook "post-stop" ''
+                echo "postStop $GREETING $EXIT_CODE $EXIT_STATUS" >> hooks
+              '')
+            ];
+          }
+        ];
+      };
+    };
+    settings.restart.mode = "never";
+  };
+in
+runCommandLocal "hooks-run-around-service" { } ''
+  set -euo pipefail
+
+  timeout 20 ${lib.getExe nimiWrapper} &> nimi_logs.txt
+
+  hooks="$(tr '\n' ',' < hooks)"
+  if [ "preStart hello,postStart hello,postStop hello exited 3," != "$hooks" ]; then
+    echo "Hooks did not run as expected, got: $hooks"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully ran hooks around the service"
+  mkdir "$out"
+''
@@ -0,0 +1,84 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  testers,
+  util-linux,
+  procps,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."main".process.argv = [
+      (lib.getExe (writeShellApplication {
+        name = "main";
+        runtimeInputs = [ util-linux ];
+        text = ''
+          trap 'echo "main stopped" >> stopped; exit 0' TERM
+          # Both outlive the service as orphans outside of its process group
+          (setsid bash -c 'trap "echo \"polite stopped\" >> stopped; exit 0" TERM
+            while true; do sleep 0.1; done' &> /dev/null &)
+          (setsid bash -c 'trap "" TERM; touch stubborn-started
+            while true; do sleep 0.1; done' &> /dev/null &)
+          touch started
+          while true; do
+            sleep 0.1 &
+            wait || true
+          done
+        '';
+      }))
+    ];
+    settings = {
+      stopTimeout = 500;
+      restart.mode = "never";
+    };
+  };
+
+  runAsPid1 = writeShellApplication {
+    name = "run-as-pid-1";
+    runtimeInputs = [
+      util-linux
+      procps
+    ];
+    text = ''
+      cd "$(mktemp -d)"
+
+      unshare --pid --fork --mount-proc ${lib.getExe nimiWrapper} &> nimi_logs.txt &
+      unshare_pid=$!
+
+      for _ in $(seq 100); do
+        [ -e started ] && [ -e stubborn-started ] && break
+        sleep 0.1
+      done
+
+      kill -s RTMIN+3 "$(pgrep -P "$unshare_pid")"
+      if ! wait "$unshare_pid"; then
+        echo "Expected nimi to shut down gracefully on SIGRTMIN+3"
+        echo "nimi logs: $(cat nimi_logs.txt)"
+        exit 1
+      fi
+
+      stopped="$(tr '\n' ' ' < stopped)"
+      if [ "main stopped polite stopped " != "$stopped" ]; then
+        echo "Expected main and then the leftover processes to be stopped, got: $stopped"
+        echo "nimi logs: $(cat nimi_logs.txt)"
+        exit 1
+      fi
+
+      if ! grep -q "Stopping remaining processes" nimi_logs.txt; then
+        echo "Expected nimi to stop the remaining processes"
+        echo "nimi logs: $(cat nimi_logs.txt)"
+        exit 1
+      fi
+    '';
+  };
+in
+testers.runNixOSTest {
+  name = "init-mode-stops-leftover-processes";
+  nodes.machine = { };
+  testScript = ''
+    start_all()
+    machine.wait_for_unit("multi-user.target")
+
+    machine.succeed("${lib.getExe runAsPid1}")
+  '';
+}
@@ -0,0 +1,57 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."hangs" = {
+      process.argv = [
+        (lib.getExe (writeShellApplication {
+          name = "hangs";
+          text = ''
+            echo "service run"
+            touch healthy
+            sleep 0.5
+            rm healthy
+            sleep 1000
+          '';
+        }))
+      ];
+      liveness = {
+        type = "file";
+        path = "healthy";
+        interval = 100;
+        failureThreshold = 2;
+      };
+    };
+    settings.restart = {
+      mode = "up-to-count";
+      count = 1;
+      time = 100;
+    };
+  };
+in
+runCommandLocal "liveness-failure-restarts-service" { } ''
+  set -euo pipefail
+
+  timeout 60 ${lib.getExe nimiWrapper} &> nimi_logs.txt
+
+  runs="$(grep -c "service run" nimi_logs.txt || true)"
+  if [ "$runs" != 2 ]; then
+    echo "Expected the unhealthy service to run twice, got $runs runs"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  stops="$(grep -c "stopped after 2 failed liveness probes" nimi_logs.txt || true)"
+  if [ "$stops" != 2 ]; then
+    echo "Expected the unhealthy service to be stopped twice, got $stops stops"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully restarted a service which stopped responding"
+  mkdir "$out"
+''
@@ -0,0 +1,64 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."flaky" = {
+      process.argv = [
+        (lib.getExe (writeShellApplication {
+          name = "flaky";
+          text = ''
+            exit 1
+          '';
+        }))
+      ];
+    };
+    services."steady" = {
+      process.argv = [
+        (lib.getExe (writeShellApplication {
+          name = "steady";
+          text = ''
+            sleep 1
+            echo "steady is still running"
+            touch steady-done
+            sleep 1000
+          '';
+        }))
+      ];
+    };
+    settings.restart.mode = "never";
+  };
+in
+runCommandLocal "non-critical-failure-keeps-others-running" { } ''
+  set -euo pipefail
+
+  ${lib.getExe nimiWrapper} &> nimi_logs.txt &
+  nimi_pid=$!
+
+  for _ in $(seq 100); do
+    [ -e steady-done ] && break
+    sleep 0.1
+  done
+
+  # Exits with 0 after a graceful shutdown, as flaky isn't critical
+  kill -TERM "$nimi_pid"
+  wait "$nimi_pid"
+
+  if ! grep -q "Service flaky failed, other services keep running" nimi_logs.txt; then
+    echo "The failure of flaky was not logged"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  if ! grep -q "steady is still running" nimi_logs.txt; then
+    echo "steady was stopped after flaky failed"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully kept services running after a non critical failure"
+  mkdir "$out"
+''
@@ -0,0 +1,85 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+  socat,
+  util-linux,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."daemon" = {
+      process = {
+        type = "notify";
+        argv = [
+          (lib.getExe (writeShellApplication {
+            name = "daemon";
+            runtimeInputs = [
+              socat
+              util-linux
+            ];
+            text = ''
+              sleep 0.5
+              touch daemon-ready
+
+              # Fork a daemon into its own session, which nimi only knows of
+              # through MAINPID
+              (
+                setsid sleep 1000 &
+                echo "$!" > main-pid
+                printf 'MAINPID=%s\nREADY=1' "$!" | socat - "UNIX-SENDTO:$NOTIFY_SOCKET"
+              )
+
+              sleep 1000
+            '';
+          }))
+        ];
+      };
+    };
+    services."app" = {
+      process.argv = [
+        (lib.getExe (writeShellApplication {
+          name = "app";
+          text = ''
+            if [ -e daemon-ready ]; then
+              echo "daemon was ready before app started"
+            fi
+            touch app-started
+            sleep 1000
+          '';
+        }))
+      ];
+      requires = [ "daemon" ];
+    };
+    settings.restart.mode = "never";
+  };
+in
+runCommandLocal "notify-service-reports-ready-and-main-pid" { } ''
+  set -euo pipefail
+
+  ${lib.getExe nimiWrapper} &> nimi_logs.txt &
+  nimi_pid=$!
+
+  for _ in $(seq 100); do
+    [ -e app-started ] && break
+    sleep 0.1
+  done
+
+  kill -TERM "$nimi_pid"
+  wait "$nimi_pid"
+
+  if ! grep -q "daemon was ready before app started" nimi_logs.txt; then
+    echo "app was started before daemon sent READY=1"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  if kill -0 "$(cat main-pid)" 2> /dev/null; then
+    echo "The main pid reported by daemon was not stopped"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully started after READY=1 and stopped the reported main pid"
+  mkdir "$out"
+''
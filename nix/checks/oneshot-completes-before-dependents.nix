@@ -0,0 +1,94 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."migrate" = {
+      process = {
+        type = "oneshot";
+        argv = [
+          (lib.getExe (writeShellApplication {
+            name = "migrate";
+            text = ''
+              sleep 0.5
+              touch migrated
+            '';
+          }))
+        ];
+      };
+    };
+    services."app" = {
+      process.argv = [
+        (lib.getExe (writeShellApplication {
+          name = "app";
+          text = ''
+            if [ -e migrated ]; then
+              echo "migrate completed before app started"
+            fi
+            touch app-started
+            sleep 1000
+          '';
+        }))
+      ];
+      requires = [ "migrate" ];
+    };
+    services."broken-migrate" = {
+      process = {
+        type = "oneshot";
+        argv = [
+          (lib.getExe (writeShellApplication {
+            name = "broken-migrate";
+            text = ''
+              exit 1
+            '';
+          }))
+        ];
+      };
+    };
+    services."blocked" = {
+      process.argv = [
+        (lib.getExe (writeShellApplication {
+          name = "blocked";
+          text = ''
+            echo "blocked started"
+            sleep 1000
+          '';
+        }))
+      ];
+      requires = [ "broken-migrate" ];
+    };
+    settings.restart.mode = "never";
+  };
+in
+runCommandLocal "oneshot-completes-before-dependents" { } ''
+  set -euo pipefail
+
+  ${lib.getExe nimiWrapper} &> nimi_logs.txt &
+  nimi_pid=$!
+
+  for _ in $(seq 100); do
+    [ -e app-started ] && break
+    sleep 0.1
+  done
+
+  kill -TERM "$nimi_pid"
+  wait "$nimi_pid"
+
+  if ! grep -q "migrate completed before app started" nimi_logs.txt; then
+    echo "app was started before migrate completed"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  if grep -q "blocked started" nimi_logs.txt; then
+    echo "blocked was started even though broken-migrate failed"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully started dependents of oneshot services once they completed"
+  mkdir "$out"
+''
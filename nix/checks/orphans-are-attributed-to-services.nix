@@ -0,0 +1,62 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."forking".process.argv = [
+      (lib.getExe (writeShellApplication {
+        name = "forking";
+        text = ''
+          # Every sleep is orphaned in the process group of forking
+          for _ in $(seq 5); do
+            (sleep 0.1 &)
+          done
+          touch forked
+          exec sleep 1000
+        '';
+      }))
+    ];
+    services."quiet".process.argv = [
+      (lib.getExe (writeShellApplication {
+        name = "quiet";
+        text = ''
+          exec sleep 1000
+        '';
+      }))
+    ];
+    settings.restart.mode = "never";
+  };
+in
+runCommandLocal "orphans-are-attributed-to-services" { } ''
+  set -euo pipefail
+
+  ${lib.getExe nimiWrapper} &> nimi_logs.txt &
+  nimi_pid=$!
+
+  for _ in $(seq 100); do
+    [ -e forked ] && break
+    sleep 0.1
+  done
+  sleep 1
+
+  kill -TERM "$nimi_pid"
+  wait "$nimi_pid"
+
+  if ! grep -q "forking\] Reaped 5 orphaned processes of the service" nimi_logs.txt; then
+    echo "Expected the 5 orphans of forking to be attributed to it"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  if grep -q "quiet\] Reaped" nimi_logs.txt; then
+    echo "Expected no orphans to be attributed to quiet"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully attributed orphans to the service they came from"
+  mkdir "$out"
+''
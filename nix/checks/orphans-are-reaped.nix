@@ -0,0 +1,82 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+  procps,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."forking".process.argv = [
+      (lib.getExe (writeShellApplication {
+        name = "forking";
+        text = ''
+          # Every sleep is orphaned once its subshell exited
+          for _ in $(seq 200); do
+            (sleep 0.01 &)
+          done
+          touch forked
+          sleep 1000
+        '';
+      }))
+    ];
+    # Its exit status must never be reaped as one of the orphans
+    services."flaky" = {
+      process.argv = [
+        (lib.getExe (writeShellApplication {
+          name = "flaky";
+          text = ''
+            sleep 0.1
+            exit 7
+          '';
+        }))
+      ];
+      restart = {
+        mode = "up-to-count";
+        count = 5;
+        time = 100;
+      };
+    };
+    settings.restart.mode = "never";
+  };
+in
+runCommandLocal "orphans-are-reaped" { nativeBuildInputs = [ procps ]; } ''
+  set -euo pipefail
+
+  ${lib.getExe nimiWrapper} &> nimi_logs.txt &
+  nimi_pid=$!
+
+  for _ in $(seq 100); do
+    [ -e forked ] && break
+    sleep 0.1
+  done
+  sleep 3
+
+  zombies="$(ps -o stat= --ppid "$nimi_pid" | grep -c Z || true)"
+
+  kill -TERM "$nimi_pid"
+  wait "$nimi_pid"
+
+  if [ "$zombies" != 0 ]; then
+    echo "Expected every orphan to be reaped, got $zombies zombies"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  reaped="$(grep -c "Reaped orphaned child" nimi_logs.txt || true)"
+  if [ "$reaped" -lt 200 ]; then
+    echo "Expected at least 200 orphans to be reaped, got $reaped"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  exits="$(grep -c "flaky exited with status exit status: 7" nimi_logs.txt || true)"
+  if [ "$exits" != 6 ]; then
+    echo "Expected the first run and 5 restarts of flaky to exit with 7, got $exits"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully reaped orphans without stealing exit statuses"
+  mkdir "$out"
+''
@@ -0,0 +1,58 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+  coreutils,
+  util-linux,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."attributes".process = {
+      argv = [
+        (lib.getExe (writeShellApplication {
+          name = "attributes";
+          runtimeInputs = [
+            coreutils
+            util-linux
+          ];
+          text = ''
+            echo "nofile $(ulimit -n)"
+            echo "umask $(umask)"
+            echo "nice $(nice)"
+            echo "ioprio $(ionice)"
+            echo "oom $(cat /proc/self/oom_score_adj)"
+          '';
+        }))
+      ];
+      limits.nofile = 4096;
+      umask = "0027";
+      nice = 5;
+      ioSchedulingClass = "best-effort";
+      ioSchedulingPriority = 6;
+      oomScoreAdjust = 500;
+    };
+    settings.restart.mode = "never";
+  };
+in
+runCommandLocal "process-attributes-are-applied" { } ''
+  set -euo pipefail
+
+  timeout 60 ${lib.getExe nimiWrapper} &> nimi_logs.txt
+
+  for expected in \
+    "nofile 4096" \
+    "umask 0027" \
+    "nice 5" \
+    "ioprio best-effort: prio 6" \
+    "oom 500"; do
+    if ! grep -q "$expected" nimi_logs.txt; then
+      echo "Expected the service to report $expected"
+      echo "nimi logs: $(cat nimi_logs.txt)"
+      exit 1
+    fi
+  done
+
+  echo "Successfully applied the process attributes to the service"
+  mkdir "$out"
+''
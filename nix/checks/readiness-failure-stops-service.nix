@@ -0,0 +1,59 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."db" = {
+      process.argv = [
+        (lib.getExe (writeShellApplication {
+          name = "never-ready";
+          text = ''
+            sleep 1000
+          '';
+        }))
+      ];
+      readiness = {
+        type = "file";
+        path = "db-ready";
+        interval = 100;
+        failureThreshold = 5;
+      };
+    };
+    services."app" = {
+      process.argv = [
+        (lib.getExe (writeShellApplication {
+          name = "app";
+          text = ''
+            echo "app started"
+            sleep 1000
+          '';
+        }))
+      ];
+      requires = [ "db" ];
+    };
+    settings.restart.mode = "never";
+  };
+in
+runCommandLocal "readiness-failure-stops-service" { } ''
+  set -euo pipefail
+
+  timeout 60 ${lib.getExe nimiWrapper} &> nimi_logs.txt
+
+  if ! grep -q "stopped after 5 failed readiness probes" nimi_logs.txt; then
+    echo "Service was not stopped after its readiness probe failed 5 times"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  if grep -q "app started" nimi_logs.txt; then
+    echo "app was started even though db never became ready"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully failed a service which never became ready"
+  mkdir "$out"
+''
@@ -0,0 +1,62 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+  coreutils,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."crashes" = {
+      process.argv = [
+        (lib.getExe (writeShellApplication {
+          name = "crashes";
+          runtimeInputs = [ coreutils ];
+          text = ''
+            date +%s%3N >> starts
+            exit 1
+          '';
+        }))
+      ];
+    };
+    settings.restart = {
+      mode = "up-to-count";
+      count = 4;
+      time = 200;
+      backoff = {
+        multiplier = 2;
+        maxTime = 800;
+        jitter = 0;
+      };
+    };
+  };
+in
+runCommandLocal "restart-backoff-grows-delay" { } ''
+  set -euo pipefail
+
+  timeout 60 ${lib.getExe nimiWrapper} &> nimi_logs.txt
+
+  read -r -a gaps <<< "$(awk 'NR > 1 { printf "%d ", $1 - prev } { prev = $1 }' starts)"
+  if [ "''${#gaps[@]}" != 4 ]; then
+    echo "Expected 4 restarts, got ''${#gaps[@]}"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  # The delay doubles from 200ms until it is capped at 800ms
+  expected=(200 400 800 800)
+  for i in 0 1 2 3; do
+    if [ "''${gaps[$i]}" -lt "''${expected[$i]}" ]; then
+      echo "Restart $i came after ''${gaps[$i]}ms, expected at least ''${expected[$i]}ms"
+      exit 1
+    fi
+  done
+
+  if [ "''${gaps[3]}" -ge 1600 ]; then
+    echo "The delay was not capped at maxTime, the last restart came after ''${gaps[3]}ms"
+    exit 1
+  fi
+
+  echo "Successfully backed off between restarts"
+  mkdir "$out"
+''
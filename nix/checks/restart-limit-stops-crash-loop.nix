@@ -0,0 +1,82 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."crash-loop" = {
+      process.argv = [
+        (lib.getExe (writeShellApplication {
+          name = "crash-loop";
+          text = ''
+            echo "service run"
+            exit 1
+          '';
+        }))
+      ];
+    };
+    settings.restart = {
+      mode = "always";
+      time = 100;
+      limit = {
+        burst = 3;
+        interval = 10000;
+      };
+    };
+  };
+
+  # Exits cleanly every time, but restarting too often still counts as failing
+  exitLoopWrapper = nimi.mkNimiBin {
+    services."exit-loop" = {
+      process.argv = [
+        (lib.getExe (writeShellApplication {
+          name = "exit-loop";
+          text = ''
+            echo "service run"
+          '';
+        }))
+      ];
+      critical = true;
+    };
+    settings.restart = {
+      mode = "always";
+      condition = "always";
+      time = 100;
+      limit = {
+        burst = 3;
+        interval = 10000;
+      };
+    };
+  };
+in
+runCommandLocal "restart-limit-stops-crash-loop" { } ''
+  set -euo pipefail
+
+  timeout 60 ${lib.getExe nimiWrapper} &> nimi_logs.txt
+
+  runs="$(grep -c "service run" nimi_logs.txt || true)"
+  if [ "$runs" != 4 ]; then
+    echo "Expected the first run and 3 restarts, got $runs runs"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  if ! grep -q "hit the limit of 3 restarts" nimi_logs.txt; then
+    echo "Hitting the restart limit was not logged"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  status=0
+  timeout 60 ${lib.getExe exitLoopWrapper} &> nimi_logs.txt || status=$?
+  if [ "$status" = 0 ]; then
+    echo "Expected the critical exit-loop service to fail once it hit the restart limit"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully stopped restarting a crash looping service"
+  mkdir "$out"
+''
@@ -0,0 +1,48 @@
+{
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+  lib,
+}:
+let
+  printsEnvironment = writeShellApplication {
+    name = "prints-environment";
+    text = ''
+      echo "greeting: ''${GREETING:-unset}"
+      echo "home: ''${HOME:-unset}"
+    '';
+  };
+
+  nimiWrapper = nimi.mkNimiBin {
+    services."prints-environment" = {
+      process = {
+        argv = [
+          (lib.getExe printsEnvironment)
+        ];
+        environment.GREETING = "hello world";
+        clearEnvironment = true;
+      };
+    };
+    settings.restart.mode = "never";
+  };
+in
+runCommandLocal "service-environment-is-set" { } ''
+  set -euo pipefail
+
+  nimi_logs="$(HOME=/homeless-shelter ${lib.getExe nimiWrapper} 2>&1)"
+
+  if [[ "$nimi_logs" != *"greeting: hello world"* ]]; then
+    echo "Failed to find 'greeting: hello world' inside logs"
+    echo "nimi logs: $nimi_logs"
+    exit 1
+  fi
+
+  if [[ "$nimi_logs" != *"home: unset"* ]]; then
+    echo "Inherited environment was not cleared"
+    echo "nimi logs: $nimi_logs"
+    exit 1
+  fi
+
+  echo "Successfully found service environment inside logs"
+  mkdir "$out"
+''
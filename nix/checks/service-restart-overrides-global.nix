@@ -0,0 +1,54 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  mkFailing =
+    name:
+    lib.getExe (writeShellApplication {
+      inherit name;
+      text = ''
+        echo "${name} run"
+        exit 1
+      '';
+    });
+
+  nimiWrapper = nimi.mkNimiBin {
+    services."overrides" = {
+      process.argv = [ (mkFailing "overrides") ];
+      restart = {
+        mode = "up-to-count";
+        count = 2;
+        time = 100;
+      };
+    };
+    services."inherits" = {
+      process.argv = [ (mkFailing "inherits") ];
+    };
+    settings.restart.mode = "never";
+  };
+in
+runCommandLocal "service-restart-overrides-global" { } ''
+  set -euo pipefail
+
+  timeout 60 ${lib.getExe nimiWrapper} &> nimi_logs.txt
+
+  overrides="$(grep -c "overrides run" nimi_logs.txt || true)"
+  if [ "$overrides" != 3 ]; then
+    echo "Expected the service overriding the restart mode to run 3 times, got $overrides"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  inherits="$(grep -c "inherits run" nimi_logs.txt || true)"
+  if [ "$inherits" != 1 ]; then
+    echo "Expected the service using the global restart mode to run once, got $inherits"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully applied per service restart overrides"
+  mkdir "$out"
+''
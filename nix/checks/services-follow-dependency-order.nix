@@ -0,0 +1,77 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  mkService =
+    name: text:
+    lib.getExe (writeShellApplication {
+      inherit name;
+      text = ''
+        trap 'echo "${name}" >> stop-order; exit 0' TERM
+        ${text}
+        sleep 1000 &
+        wait
+      '';
+    });
+
+  nimiWrapper = nimi.mkNimiBin {
+    services."db" = {
+      process.argv = [
+        (mkService "db" ''
+          sleep 0.5
+          touch db-ready
+        '')
+      ];
+      readiness = {
+        type = "file";
+        path = "db-ready";
+        interval = 100;
+      };
+    };
+    services."app" = {
+      process.argv = [
+        (mkService "app" ''
+          if [ -e db-ready ]; then
+            echo "db was ready before app started"
+          fi
+          touch app-started
+        '')
+      ];
+      requires = [ "db" ];
+    };
+    settings.restart.mode = "never";
+  };
+in
+runCommandLocal "services-follow-dependency-order" { } ''
+  set -euo pipefail
+
+  ${lib.getExe nimiWrapper} &> nimi_logs.txt &
+  nimi_pid=$!
+
+  for _ in $(seq 100); do
+    [ -e app-started ] && break
+    sleep 0.1
+  done
+
+  kill -TERM "$nimi_pid"
+  wait "$nimi_pid"
+
+  if ! grep -q "db was ready before app started" nimi_logs.txt; then
+    echo "app was started before db was ready"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  stop_order="$(tr '\n' ' ' < stop-order)"
+  if [ "app db " != "$stop_order" ]; then
+    echo "Services were not stopped in reverse dependency order, got: $stop_order"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully started and stopped services in dependency order"
+  mkdir "$out"
+''
@@ -0,0 +1,104 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."service-a".process.argv = [
+      (lib.getExe (writeShellApplication {
+        name = "service-a";
+        text = ''
+          echo "cached" > cache-file
+        '';
+      }))
+    ];
+    settings.restart.mode = "never";
+    settings.shutdown.steps = [
+      {
+        argv = [
+          (lib.getExe (writeShellApplication {
+            name = "flush-cache";
+            text = ''
+              cat cache-file
+            '';
+          }))
+        ];
+        timeout = 10000;
+      }
+    ];
+    settings.logging = {
+      enable = true;
+      logsDir = "my_logs";
+    };
+  };
+
+  failedStartupWrapper = nimi.mkNimiBin {
+    services."service-b".process.argv = [
+      (lib.getExe (writeShellApplication {
+        name = "service-b";
+        text = ''
+          echo "service-b started"
+        '';
+      }))
+    ];
+    settings.restart.mode = "never";
+    settings.startup.steps = [
+      {
+        argv = [
+          (lib.getExe (writeShellApplication {
+            name = "failing-migration";
+            text = ''
+              exit 1
+            '';
+          }))
+        ];
+      }
+    ];
+    settings.shutdown.steps = [
+      {
+        argv = [
+          (lib.getExe (writeShellApplication {
+            name = "clean-up";
+            text = ''
+              echo "cleaned up"
+            '';
+          }))
+        ];
+      }
+    ];
+    settings.logging = {
+      enable = true;
+      logsDir = "failed_logs";
+    };
+  };
+in
+runCommandLocal "shutdown-steps-run-after-services" { } ''
+  set -euo pipefail
+
+  echo "${lib.getExe nimiWrapper}"
+  ${lib.getExe nimiWrapper}
+
+  flush_logs="$(cat my_logs/logs-0/shutdown-1.txt)"
+  if [ "cached" != "$flush_logs" ]; then
+    echo "Got incorrect output from shutdown step 1"
+    echo "Contents: $flush_logs"
+    exit 1
+  fi
+
+  if ${lib.getExe failedStartupWrapper}; then
+    echo "nimi succeeded even though a startup step failed"
+    exit 1
+  fi
+
+  clean_up_logs="$(cat failed_logs/logs-0/shutdown-1.txt)"
+  if [ "cleaned up" != "$clean_up_logs" ]; then
+    echo "Shutdown step did not run after a startup step failed"
+    echo "Contents: $clean_up_logs"
+    exit 1
+  fi
+
+  echo "Successfully ran all shutdown steps"
+  mkdir "$out"
+''
@@ -0,0 +1,61 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  mkService =
+    name:
+    lib.getExe (writeShellApplication {
+      inherit name;
+      text = ''
+        trap 'echo "${name}" >> reloaded' HUP
+        touch "${name}-started"
+        while true; do
+          sleep 0.1 &
+          wait || true
+        done
+      '';
+    });
+
+  nimiWrapper = nimi.mkNimiBin {
+    services."reloading" = {
+      process.argv = [ (mkService "reloading") ];
+      forwardSignals = [ "SIGHUP" ];
+    };
+    services."other".process.argv = [ (mkService "other") ];
+    settings.restart.mode = "never";
+  };
+in
+runCommandLocal "signals-are-forwarded" { } ''
+  set -euo pipefail
+
+  ${lib.getExe nimiWrapper} &> nimi_logs.txt &
+  nimi_pid=$!
+
+  for _ in $(seq 100); do
+    [ -e reloading-started ] && [ -e other-started ] && break
+    sleep 0.1
+  done
+
+  kill -HUP "$nimi_pid"
+  for _ in $(seq 100); do
+    [ -e reloaded ] && break
+    sleep 0.1
+  done
+  sleep 1
+
+  kill -TERM "$nimi_pid"
+  wait "$nimi_pid"
+
+  reloaded="$(tr '\n' ' ' < reloaded)"
+  if [ "reloading " != "$reloaded" ]; then
+    echo "Expected SIGHUP to only be forwarded to reloading, got: $reloaded"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully forwarded signals to the services asking for them"
+  mkdir "$out"
+''
@@ -0,0 +1,78 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  stepPrinter = writeShellApplication {
+    name = "step-printer";
+    text = ''
+      echo "$1 $GREETING"
+    '';
+  };
+
+  flakyStep = writeShellApplication {
+    name = "flaky-step";
+    text = ''
+      if [ ! -f flaky-marker ]; then
+        touch flaky-marker
+        exit 1
+      fi
+      echo "flaky succeeded"
+    '';
+  };
+
+  nimiWrapper = nimi.mkNimiBin {
+    services."service-a".process.argv = [
+      (lib.getExe (writeShellApplication {
+        name = "service-a";
+        text = ''
+          echo "Hello from service A"
+        '';
+      }))
+    ];
+    settings.restart.mode = "never";
+    settings.startup.steps = [
+      {
+        argv = [
+          (lib.getExe stepPrinter)
+          "first"
+        ];
+        environment.GREETING = "step";
+      }
+      {
+        argv = [ (lib.getExe flakyStep) ];
+        retries = 1;
+        timeout = 10000;
+      }
+    ];
+    settings.logging = {
+      enable = true;
+      logsDir = "my_logs";
+    };
+  };
+in
+runCommandLocal "startup-steps-run-in-order" { } ''
+  set -euo pipefail
+
+  echo "${lib.getExe nimiWrapper}"
+  ${lib.getExe nimiWrapper}
+
+  first_logs="$(cat my_logs/logs-0/startup-1.txt)"
+  if [ "first step" != "$first_logs" ]; then
+    echo "Got incorrect output from startup step 1"
+    echo "Contents: $first_logs"
+    exit 1
+  fi
+
+  flaky_logs="$(cat my_logs/logs-0/startup-2.txt)"
+  if [ "flaky succeeded" != "$flaky_logs" ]; then
+    echo "Got incorrect output from startup step 2"
+    echo "Contents: $flaky_logs"
+    exit 1
+  fi
+
+  echo "Successfully ran all startup steps"
+  mkdir "$out"
+''
@@ -0,0 +1,56 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+  util-linux,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."forking" = {
+      process.argv = [
+        (lib.getExe (writeShellApplication {
+          name = "forking";
+          runtimeInputs = [ util-linux ];
+          text = ''
+            # Stays in the process group, but ignores SIGTERM
+            bash -c 'trap "" TERM; while true; do sleep 0.1; done' &
+            echo "$!" >> pids
+            # Leaves the process group in a session of its own
+            setsid sleep 1000 &
+            echo "$!" >> pids
+            touch started
+            sleep 1000
+          '';
+        }))
+      ];
+      stopTimeout = 500;
+    };
+    settings.restart.mode = "never";
+  };
+in
+runCommandLocal "stop-reaches-every-descendant" { } ''
+  set -euo pipefail
+
+  ${lib.getExe nimiWrapper} &> nimi_logs.txt &
+  nimi_pid=$!
+
+  for _ in $(seq 100); do
+    [ -e started ] && break
+    sleep 0.1
+  done
+
+  kill -TERM "$nimi_pid"
+  wait "$nimi_pid"
+
+  while read -r pid; do
+    if kill -0 "$pid" 2> /dev/null; then
+      echo "Process $pid of forking is still running after nimi stopped"
+      echo "nimi logs: $(cat nimi_logs.txt)"
+      exit 1
+    fi
+  done < pids
+
+  echo "Successfully stopped every descendant of a service"
+  mkdir "$out"
+''
@@ -0,0 +1,57 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."worker" = {
+      process.argv = [
+        (lib.getExe (writeShellApplication {
+          name = "worker";
+          text = ''
+            trap 'echo usr1 >> signals' USR1
+            trap 'echo term >> signals; exit 0' TERM
+            touch started
+            while true; do
+              sleep 0.1 &
+              wait || true
+            done
+          '';
+        }))
+      ];
+      # Ignores the first signal, so nimi has to escalate to the next one
+      stopSignals = [
+        "SIGUSR1"
+        "SIGTERM"
+      ];
+      stopTimeout = 500;
+    };
+    settings.restart.mode = "never";
+  };
+in
+runCommandLocal "stop-signals-escalate" { } ''
+  set -euo pipefail
+
+  ${lib.getExe nimiWrapper} &> nimi_logs.txt &
+  nimi_pid=$!
+
+  for _ in $(seq 100); do
+    [ -e started ] && break
+    sleep 0.1
+  done
+
+  kill -TERM "$nimi_pid"
+  wait "$nimi_pid"
+
+  signals="$(tr '\n' ' ' < signals)"
+  if [ "usr1 term " != "$signals" ]; then
+    echo "Expected worker to get SIGUSR1 and then SIGTERM, got: $signals"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully escalated through the stop signals of a service"
+  mkdir "$out"
+''
@@ -0,0 +1,56 @@
+{
+  lib,
+  writeShellApplication,
+  nimi,
+  runCommandLocal,
+  coreutils,
+}:
+let
+  nimiWrapper = nimi.mkNimiBin {
+    services."stubborn" = {
+      process.argv = [
+        (lib.getExe (writeShellApplication {
+          name = "stubborn";
+          text = ''
+            trap "" TERM
+            touch started
+            while true; do
+              sleep 0.1
+            done
+          '';
+        }))
+      ];
+      # Overrides the global stop timeout below
+      stopTimeout = 500;
+    };
+    settings = {
+      stopTimeout = 60000;
+      restart.mode = "never";
+    };
+  };
+in
+runCommandLocal "stop-timeout-kills-stubborn-service" { nativeBuildInputs = [ coreutils ]; } ''
+  set -euo pipefail
+
+  ${lib.getExe nimiWrapper} &> nimi_logs.txt &
+  nimi_pid=$!
+
+  for _ in $(seq 100); do
+    [ -e started ] && break
+    sleep 0.1
+  done
+
+  start="$(date +%s%3N)"
+  kill -TERM "$nimi_pid"
+  wait "$nimi_pid"
+  took="$(( $(date +%s%3N) - start ))"
+
+  if [ "$took" -lt 500 ] || [ "$took" -ge 30000 ]; then
+    echo "Expected stubborn to be killed after its 500ms stop timeout, took ''${took}ms"
+    echo "nimi logs: $(cat nimi_logs.txt)"
+    exit 1
+  fi
+
+  echo "Successfully killed a service ignoring SIGTERM after its stop timeout"
+  mkdir "$out"
+''
@@ -0,0 +1,45 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+in
+{
+  _class = "nimi";
+
+  options.settings.cgroups = mkOption {
+    description = ''
+      Cgroup v2 settings for the nimi process manager.
+
+      When enabled, nimi has to be given a delegated cgroup v2 subtree, as
+      containers with their own cgroup namespace and systemd units with
+      `Delegate=yes` do. nimi moves itself into the `nimi.scope` child of its
+      cgroup and creates a `<name>.service` child per service, which every
+      process of the service is placed in. Its `hooks` and exec probes run
+      outside of it, in the cgroup of nimi, so they aren't limited along with
+      the service. Once the service stopped for good, processes left in its
+      cgroup are killed and the cgroup is removed.
+
+      Stopping a service then reaches every process in its cgroup, and nimi
+      logs how much CPU time and memory each service used once it stopped.
+      The limits in `services.<name>.process` (`memoryMax`, `cpuMax` and
+      `pidsMax`) need this to be enabled.
+
+      The `--tui` frontend doesn't support cgroups.
+    '';
+    example = lib.literalExpression ''
+      {
+        enable = true;
+      }
+    '';
+    type = types.submodule {
+      options.enable = mkOption {
+        description = ''
+          Create a cgroup per service below the cgroup nimi runs in.
+        '';
+        type = types.bool;
+        default = false;
+        example = lib.literalExpression "true";
+      };
+    };
+    default = { };
+  };
+}
@@ -0,0 +1,85 @@
+{ lib, config, ... }:
+let
+  inherit (lib) mkOption types;
+  inherit (config.settings.exit) codeFrom;
+in
+{
+  _class = "nimi";
+
+  options.settings.exit = mkOption {
+    description = ''
+      Exit code behavior for the nimi process manager.
+
+      When a critical service fails, nimi stops the remaining services and
+      exits with the exit code of the failed service, or `128 + signal` if it
+      was killed by a signal, just like a shell or `tini` would. This lets
+      orchestrators and CI checks see the real reason a container stopped.
+
+      A graceful shutdown (e.g. after `SIGTERM`) exits with `0`.
+
+      `abortOnExit` and `codeFrom` turn nimi into a test runner, like
+      docker-compose's `--abort-on-container-exit` and `--exit-code-from`:
+      a single "test" service drives the others and decides the result.
+    '';
+    example = lib.literalExpression ''
+      {
+        remapToSuccess = [ 143 ];
+        codeFrom = "integration-tests";
+      }
+    '';
+    type = types.submodule {
+      options = {
+        remapToSuccess = mkOption {
+          description = ''
+            Exit codes which nimi reports as `0` instead.
+
+            Equivalent to `tini -e`. Useful for programs which exit with a non
+            zero code after a deliberate shutdown, such as JVM applications
+            exiting with `143` after `SIGTERM`.
+          '';
+          type = types.listOf (types.ints.between 0 255);
+          default = [ ];
+          example = lib.literalExpression "[ 143 ]";
+        };
+        abortOnExit = mkOption {
+          description = ''
+            Stop every service as soon as any service exits for good, even
+            successfully, and exit with the exit code of that service.
+
+            A service exits for good once it won't be restarted again.
+          '';
+          type = types.bool;
+          default = false;
+          example = lib.literalExpression "true";
+        };
+        codeFrom = mkOption {
+          description = ''
+            Name of a service whose exit stops every service and decides the
+            exit code of nimi.
+
+            Once the service exits for good, the remaining services are stopped
+            and nimi exits with the service's exit code, also when it is `0`.
+            A service which failed without a failed exit code of its own, e.g.
+            because a service it `requires` or its readiness probe failed,
+            makes nimi exit with `1`.
+            If the service was itself stopped by nimi, e.g. because of
+            `abortOnExit`, nimi exits with `0`.
+
+            Set to `null` to disable.
+          '';
+          type = types.nullOr types.str;
+          default = null;
+          example = lib.literalExpression ''"integration-tests"'';
+        };
+      };
+    };
+    default = { };
+  };
+
+  config.assertions = [
+    {
+      assertion = codeFrom == null || config.services ? ${codeFrom};
+      message = "settings.exit.codeFrom must reference a defined service.";
+    }
+  ];
+}
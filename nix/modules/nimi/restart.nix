@@ -1,6 +1,8 @@
 { lib, ... }:
 let
   inherit (lib) mkOption types;
+
+  restartLimit = import ../restart-limit.nix { inherit lib; };
 in
 {
   _class = "nimi";
@@ -20,8 +22,15 @@ in
     example = lib.literalExpression ''
       {
         mode = "up-to-count";
+        condition = "on-failure";
         time = 500;
         count = 3;
+        backoff.maxTime = 30000;
+        resetAfter = 60000;
+        limit = {
+          burst = 5;
+          interval = 10000;
+        };
       }
     '';
     type = types.submodule {
@@ -47,6 +56,30 @@ in
             "always"
           ];
         };
+        condition = mkOption {
+          description = ''
+            Selects which exits trigger a restart, like systemd's `Restart=`.
+
+            - `always`: restart after every exit.
+            - `on-success`: restart only after clean exits, meaning exit code
+              `0` or one of the service's `successExitStatus` entries.
+            - `on-failure`: restart after unclean exit codes and abnormal
+              exits.
+            - `on-abnormal`: restart only after abnormal exits, meaning the
+              process was killed by a signal or stopped for failing its
+              liveness probe or watchdog.
+
+            `mode` still decides how often a service may be restarted.
+          '';
+          default = "on-failure";
+          example = lib.literalExpression ''"always"'';
+          type = types.enum [
+            "always"
+            "on-success"
+            "on-failure"
+            "on-abnormal"
+          ];
+        };
         time = mkOption {
           description = ''
             Delay between restarts in milliseconds.
@@ -69,6 +102,96 @@ in
           default = 5;
           example = lib.literalExpression "3";
         };
+        backoff = mkOption {
+          description = ''
+            Exponential backoff for the delay between restarts.
+
+            When set, `time` becomes the initial delay and every consecutive
+            restart multiplies it by `multiplier`, up to `maxTime`. This keeps
+            a crash-looping service from flooding logs and hammering its
+            downstream dependencies.
+
+            Set to `null` to always wait exactly `time` between restarts.
+          '';
+          type = types.nullOr (
+            types.submodule {
+              options = {
+                multiplier = mkOption {
+                  description = ''
+                    Factor the delay grows by with every consecutive restart.
+                  '';
+                  type = types.addCheck types.number (x: x >= 1) // {
+                    description = "number greater than or equal to 1";
+                  };
+                  default = 2;
+                  example = lib.literalExpression "1.5";
+                };
+                maxTime = mkOption {
+                  description = ''
+                    Upper bound for the delay between restarts in milliseconds.
+                  '';
+                  type = types.ints.positive;
+                  default = 60000;
+                  example = lib.literalExpression "30000";
+                };
+                jitter = mkOption {
+                  description = ''
+                    Fraction of each delay to randomly add or subtract.
+
+                    Spreads out the restarts of services which failed at the
+                    same time, e.g. `0.1` varies each delay by up to 10%.
+                  '';
+                  type = types.numbers.between 0 1;
+                  default = 0.1;
+                  example = lib.literalExpression "0.25";
+                };
+              };
+            }
+          );
+          default = null;
+          example = lib.literalExpression ''
+            {
+              multiplier = 2;
+              maxTime = 30000;
+              jitter = 0.1;
+            }
+          '';
+        };
+        resetAfter = mkOption {
+          description = ''
+            Time in milliseconds a service has to stay up before its restart
+            count (and backoff) is reset.
+
+            Without this a service which crashes once a day eventually uses up
+            its `up-to-count` restarts. Set to `null` to never reset.
+          '';
+          type = types.nullOr types.ints.positive;
+          default = null;
+          example = lib.literalExpression "60000";
+        };
+        limit = mkOption {
+          description = ''
+            Rate limit for restarts, like systemd's `StartLimitBurst` and
+            `StartLimitIntervalSec`.
+
+            A service may be restarted at most `burst` times within any
+            `interval`. Once it exceeds that it is considered failed and is
+            not restarted again. Unlike `count`, restarts which happened longer
+            than `interval` ago no longer count against the service.
+
+            The limit applies on top of `mode`, so combining it with `always`
+            gives a service unlimited restarts as long as it doesn't crash
+            loop. Set to `null` to disable.
+          '';
+          type = types.nullOr (types.submodule { inherit (restartLimit) options; });
+          default = null;
+          example = lib.literalExpression ''
+            {
+              burst = 5;
+              interval = 10000;
+            }
+          '';
+        };
       };
     };
     default = { };
@@ -0,0 +1,64 @@
+{ lib, config, ... }:
+let
+  inherit (lib) mkOption types;
+
+  probe = import ../probe.nix { inherit lib; };
+in
+{
+  _class = "nimi";
+
+  # Extends the upstream modular service submodule with the nimi specific
+  # options found in `../service`.
+  options.services = mkOption {
+    type = types.lazyAttrsOf (
+      types.submoduleWith {
+        modules = lib.filesystem.listFilesRecursive ../service;
+      }
+    );
+  };
+
+  config.assertions = lib.concatLists (
+    lib.mapAttrsToList (
+      name: service:
+      [
+        {
+          assertion =
+            !(lib.elem name [
+              ""
+              "."
+              ".."
+            ])
+            && !(lib.hasInfix "/" name);
+          message = "services.\"${name}\" must not be empty, `.` or `..`, or contain `/`, as service names are used in file paths.";
+        }
+        {
+          assertion = lib.all (x: lib.match "[A-Za-z_][A-Za-z0-9_]*" x != null) (
+            lib.attrNames service.process.environment
+          );
+          message = "services.${name}.process.environment must only contain valid environment variable names.";
+        }
+        {
+          assertion = lib.all (dependency: config.services ? ${dependency}) (
+            service.after ++ service.requires
+          );
+          message = "services.${name}.after and services.${name}.requires must only reference defined services.";
+        }
+        {
+          assertion = service.process.type != "notify" || service.readiness == null;
+          message = "services.${name}.readiness can't be combined with services.${name}.process.type = \"notify\", readiness is reported over the notify socket.";
+        }
+        {
+          assertion =
+            service.process.type != "oneshot" || (service.readiness == null && service.liveness == null);
+          message = "services.${name}.readiness and services.${name}.liveness can't be combined with services.${name}.process.type = \"oneshot\", oneshot services are started once they exit.";
+        }
+      ]
+      ++ lib.optionals (service.readiness != null) (
+        probe.assertionsFor "services.${name}.readiness" service.readiness
+      )
+      ++ lib.optionals (service.liveness != null) (
+        probe.assertionsFor "services.${name}.liveness" service.liveness
+      )
+    ) config.services
+  );
+}
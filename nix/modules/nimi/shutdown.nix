@@ -0,0 +1,56 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+
+  step = import ../step.nix { inherit lib; };
+in
+{
+  _class = "nimi";
+
+  options.settings.shutdown = mkOption {
+    description = ''
+      Shutdown behavior for the nimi process manager.
+
+      This section mirrors `settings.startup`: the `steps` run in order once
+      every service has stopped, right before nimi exits. It is useful for
+      flushing caches to a persistent volume or uploading final artifacts
+      when a container is terminated.
+
+      Every step runs even if an earlier one failed. If any of them fails,
+      nimi exits with an error. Output is streamed to the console and, when
+      `settings.logging` is enabled, written to `shutdown-<n>.txt` log files.
+    '';
+    example = lib.literalExpression ''
+      {
+        steps = [
+          {
+            argv = [ (lib.getExe my-uploader) "--artifacts" "/data/out" ];
+            timeout = 30000;
+          }
+        ];
+      }
+    '';
+    type = types.submodule {
+      options.steps = mkOption {
+        description = ''
+          Ordered list of commands to run after all services stopped.
+
+          Each step takes a full `argv`, its own `environment`, a `timeout`,
+          and a number of `retries`. Step `n` (counting from 1) logs under the
+          `shutdown-<n>` target.
+        '';
+        type = types.listOf (types.submodule { inherit (step) options; });
+        default = [ ];
+        example = lib.literalExpression ''
+          [
+            {
+              argv = [ (lib.getExe my-uploader) "--artifacts" "/data/out" ];
+              timeout = 30000;
+            }
+          ]
+        '';
+      };
+    };
+    default = { };
+  };
+}
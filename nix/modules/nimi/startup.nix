@@ -1,6 +1,8 @@
 { lib, ... }:
 let
   inherit (lib) mkOption types;
+
+  step = import ../step.nix { inherit lib; };
 in
 {
   _class = "nimi";
@@ -9,17 +11,28 @@ in
     description = ''
       Startup behavior for the nimi process manager.
 
-      This section lets you run a one-time initialization command before any
+      This section lets you run one-time initialization commands before any
       configured services are started. It is useful for bootstrapping state,
-      preparing directories, or running a short setup task that should happen
+      preparing directories, or running short setup tasks that should happen
       once per process manager start.
 
-      The command is executed once and then the normal service startup proceeds.
-      If you do not need a startup hook, leave it unset.
+      `runOnStartup` runs first, followed by each of the `steps` in order.
+      Once all of them succeeded the normal service startup proceeds. If any
+      of them fails, nimi exits without starting services.
+
+      Output is streamed to the console and, when `settings.logging` is
+      enabled, written to `startup.txt` and `startup-<n>.txt` log files.
     '';
     example = lib.literalExpression ''
       {
         runOnStartup = /nix/store/abcd1234-my-init/bin/my-init;
+        steps = [
+          {
+            argv = [ (lib.getExe my-migrations) "--apply" ];
+            timeout = 60000;
+            retries = 3;
+          }
+        ];
       }
     '';
     type = types.submodule {
@@ -51,6 +64,27 @@ in
             )
           '';
         };
+        steps = mkOption {
+          description = ''
+            Ordered list of commands to run at startup, after `runOnStartup`.
+
+            Unlike `runOnStartup`, each step takes a full `argv`, its own
+            `environment`, a `timeout`, and a number of `retries` for commands
+            which can fail transiently, such as waiting on a remote database.
+            Step `n` (counting from 1) logs under the `startup-<n>` target.
+          '';
+          type = types.listOf (types.submodule { inherit (step) options; });
+          default = [ ];
+          example = lib.literalExpression ''
+            [
+              {
+                argv = [ (lib.getExe my-migrations) "--apply" ];
+                timeout = 60000;
+                retries = 3;
+              }
+            ]
+          '';
+        };
       };
     };
     default = { };
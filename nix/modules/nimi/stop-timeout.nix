@@ -0,0 +1,23 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+in
+{
+  _class = "nimi";
+
+  options.settings.stopTimeout = mkOption {
+    description = ''
+      Time in milliseconds a process gets to exit after being asked to stop,
+      before it is killed with `SIGKILL`, mirroring systemd's
+      `TimeoutStopSec=`. For services with several `stopSignals` the wait
+      applies after each of them, so stopping can take up to this long per
+      signal.
+
+      This applies to services as well as startup steps, shutdown steps and
+      hooks. Override it per service with `services.<name>.stopTimeout`.
+    '';
+    type = types.ints.positive;
+    default = 1000;
+    example = lib.literalExpression "10000";
+  };
+}
@@ -0,0 +1,91 @@
+# Options shared by every kind of service probe (readiness, liveness, ...)
+{ lib }:
+let
+  inherit (lib) mkOption types;
+in
+{
+  options = {
+    type = mkOption {
+      description = ''
+        The kind of check to run.
+
+        - `tcp`: succeeds once a TCP connection to `address` is accepted.
+        - `http`: succeeds once a `GET` request to `url` returns a `2xx` status.
+        - `exec`: succeeds once the command in `argv` exits with code `0`.
+        - `file`: succeeds once a file exists at `path`.
+      '';
+      type = types.enum [
+        "tcp"
+        "http"
+        "exec"
+        "file"
+      ];
+      example = lib.literalExpression ''"tcp"'';
+    };
+    address = mkOption {
+      description = ''
+        Address (`host:port`) to connect to when `type` is `tcp`.
+      '';
+      type = types.nullOr types.str;
+      default = null;
+      example = lib.literalExpression ''"127.0.0.1:5432"'';
+    };
+    url = mkOption {
+      description = ''
+        URL to request when `type` is `http`.
+
+        Only plain `http://` URLs are supported.
+      '';
+      type = types.nullOr types.str;
+      default = null;
+      example = lib.literalExpression ''"http://127.0.0.1:8080/health"'';
+    };
+    argv = mkOption {
+      description = ''
+        Command to run when `type` is `exec`.
+      '';
+      type = types.nullOr (types.listOf types.str);
+      default = null;
+      example = lib.literalExpression ''[ "''${pkgs.postgresql}/bin/pg_isready" ]'';
+    };
+    path = mkOption {
+      description = ''
+        Path of the file to wait for when `type` is `file`.
+      '';
+      type = types.nullOr types.str;
+      default = null;
+      example = lib.literalExpression ''"/run/app/app.sock"'';
+    };
+    timeout = mkOption {
+      description = ''
+        Time in milliseconds a single check may take before it counts as failed.
+      '';
+      type = types.ints.positive;
+      default = 1000;
+      example = lib.literalExpression "5000";
+    };
+  };
+
+  assertionsFor =
+    optionPath: probe:
+    let
+      field =
+        {
+          tcp = "address";
+          http = "url";
+          exec = "argv";
+          file = "path";
+        }
+        .${probe.type};
+    in
+    [
+      {
+        assertion = probe.${field} != null;
+        message = "${optionPath}.${field} must be set when ${optionPath}.type is \"${probe.type}\".";
+      }
+      {
+        assertion = probe.type != "http" || probe.url == null || lib.hasPrefix "http://" probe.url;
+        message = "${optionPath}.url must be a plain http:// URL.";
+      }
+    ];
+}
@@ -0,0 +1,25 @@
+# Options for rate limiting restarts, shared by the global and per service restart settings
+{ lib }:
+let
+  inherit (lib) mkOption types;
+in
+{
+  options = {
+    burst = mkOption {
+      description = ''
+        Number of restarts allowed within `interval`.
+      '';
+      type = types.ints.positive;
+      default = 5;
+      example = lib.literalExpression "3";
+    };
+    interval = mkOption {
+      description = ''
+        Length of the sliding window restarts are counted in, in milliseconds.
+      '';
+      type = types.ints.positive;
+      default = 10000;
+      example = lib.literalExpression "60000";
+    };
+  };
+}
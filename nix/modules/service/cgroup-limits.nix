@@ -0,0 +1,45 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+in
+{
+  options.process = {
+    memoryMax = mkOption {
+      description = ''
+        Memory limit of the service, written to `memory.max` of its cgroup,
+        mirroring systemd's `MemoryMax=`. Takes bytes with an optional `K`,
+        `M`, `G` or `T` suffix, or `max`.
+
+        Needs `settings.cgroups.enable`. Set to `null` to not limit memory.
+      '';
+      type = types.nullOr (types.strMatching "([0-9]+[KMGT]?|max)");
+      default = null;
+      example = lib.literalExpression ''"512M"'';
+    };
+    cpuMax = mkOption {
+      description = ''
+        CPU limit of the service, written to `cpu.max` of its cgroup. Takes
+        the microseconds of CPU time the service may use per period,
+        optionally followed by the period in microseconds (`100000` by
+        default), or `max`. `"50000 100000"` limits the service to half a
+        CPU, like systemd's `CPUQuota=50%`.
+
+        Needs `settings.cgroups.enable`. Set to `null` to not limit CPU time.
+      '';
+      type = types.nullOr (types.strMatching "(max|[0-9]+)( [0-9]+)?");
+      default = null;
+      example = lib.literalExpression ''"200000 100000"'';
+    };
+    pidsMax = mkOption {
+      description = ''
+        Maximum number of processes and threads of the service, written to
+        `pids.max` of its cgroup, mirroring systemd's `TasksMax=`.
+
+        Needs `settings.cgroups.enable`. Set to `null` to not limit them.
+      '';
+      type = types.nullOr types.ints.positive;
+      default = null;
+      example = lib.literalExpression "512";
+    };
+  };
+}
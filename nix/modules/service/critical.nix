@@ -0,0 +1,24 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+in
+{
+  options.critical = mkOption {
+    description = ''
+      Whether a failure of this service brings down every other service.
+
+      A service fails once it won't be restarted again, e.g. because it used up
+      its restarts or a service it `requires` failed. When a critical service
+      fails nimi stops all services and exits with an error.
+
+      By default failures are only logged and the remaining services keep
+      running. Mark the services your deployment can't do without as critical,
+      so a container or unit running nimi fails visibly instead. Services
+      which can't be spawned at all, e.g. because of a wrong binary path, stop
+      nimi with an error either way.
+    '';
+    type = types.bool;
+    default = false;
+    example = lib.literalExpression "true";
+  };
+}
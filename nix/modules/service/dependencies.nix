@@ -0,0 +1,35 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+in
+{
+  options = {
+    after = mkOption {
+      description = ''
+        Names of other services which have to be started before this one.
+
+        This only affects ordering: the service waits for the listed services
+        to start, but is still started if one of them fails. On shutdown, the
+        order is reversed so this service is stopped before the services it
+        was started after.
+
+        Dependency cycles are rejected when the config is validated.
+      '';
+      type = types.listOf types.str;
+      default = [ ];
+      example = lib.literalExpression ''[ "database" ]'';
+    };
+    requires = mkOption {
+      description = ''
+        Names of other services this service can't run without.
+
+        Implies `after`, and additionally refuses to start this service if one
+        of the listed services fails to start. If a required service fails
+        while this one is running, this service is stopped as well.
+      '';
+      type = types.listOf types.str;
+      default = [ ];
+      example = lib.literalExpression ''[ "database" ]'';
+    };
+  };
+}
@@ -0,0 +1,42 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+in
+{
+  options.process = {
+    environment = mkOption {
+      description = ''
+        Environment variables to set for the service process.
+
+        These are applied on top of the environment nimi itself was started
+        with (unless `process.clearEnvironment` is set), so you no longer need
+        to wrap a service binary in a shell script just to export a few
+        variables.
+
+        `XDG_CONFIG_HOME` is always set to the service's config data directory,
+        but can be overridden here.
+      '';
+      type = types.lazyAttrsOf types.str;
+      default = { };
+      example = lib.literalExpression ''
+        {
+          RUST_LOG = "info";
+          DATABASE_URL = "postgres://localhost/app";
+        }
+      '';
+    };
+    clearEnvironment = mkOption {
+      description = ''
+        If the environment inherited from nimi should be cleared before
+        starting the service.
+
+        When enabled the service only sees `process.environment` (and
+        `XDG_CONFIG_HOME`), which is useful for keeping the runtime
+        reproducible regardless of how nimi was launched.
+      '';
+      type = types.bool;
+      default = false;
+      example = lib.literalExpression "true";
+    };
+  };
+}
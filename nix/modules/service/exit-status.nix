@@ -0,0 +1,66 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+
+  signal = import ../signal.nix { inherit lib; };
+
+  exitStatusSet = types.submodule {
+    options = {
+      codes = mkOption {
+        description = ''
+          Exit codes in the set.
+        '';
+        type = types.listOf (types.ints.between 0 255);
+        default = [ ];
+        example = lib.literalExpression "[ 75 ]";
+      };
+      signals = mkOption {
+        description = ''
+          Terminating signals in the set, by name.
+        '';
+        type = types.listOf signal;
+        default = [ ];
+        example = lib.literalExpression ''[ "SIGTERM" ]'';
+      };
+    };
+  };
+in
+{
+  options = {
+    successExitStatus = mkOption {
+      description = ''
+        Exit codes and signals which count as a clean exit for this service,
+        on top of exit code `0`.
+
+        Like systemd's `SuccessExitStatus=`. Useful for programs which use a
+        non zero code to report a deliberate exit, e.g. Java applications
+        exiting with `143` after `SIGTERM`.
+      '';
+      type = exitStatusSet;
+      default = { };
+      example = lib.literalExpression ''
+        {
+          codes = [ 143 ];
+          signals = [ "SIGTERM" ];
+        }
+      '';
+    };
+    restartPreventExitStatus = mkOption {
+      description = ''
+        Exit codes and signals after which this service is never restarted,
+        regardless of `restart.condition` and `restart.mode`.
+
+        Like systemd's `RestartPreventExitStatus=`. Use this for exit codes
+        signalling errors which a restart can't fix, such as invalid
+        configuration.
+      '';
+      type = exitStatusSet;
+      default = { };
+      example = lib.literalExpression ''
+        {
+          codes = [ 78 ];
+        }
+      '';
+    };
+  };
+}
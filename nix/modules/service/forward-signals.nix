@@ -0,0 +1,32 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+in
+{
+  options.forwardSignals = mkOption {
+    description = ''
+      Signals received by nimi which are forwarded to the service process,
+      like `tini` does for its child. Unlike `stopSignals` they only go to the
+      process itself, or to the main pid reported through `sd_notify`, and
+      not to its whole process group. Signals received while the service
+      isn't running, e.g. while it waits to be restarted, are dropped.
+
+      This lets reload-capable services such as nginx or haproxy be reloaded
+      with e.g. `docker kill -s HUP`. Signals which no service forwards keep
+      their default behavior. `SIGINT` and `SIGTERM` always stop nimi and
+      can't be forwarded.
+
+      Not supported by the `--tui` frontend.
+    '';
+    type = types.listOf (
+      types.enum [
+        "SIGHUP"
+        "SIGUSR1"
+        "SIGUSR2"
+        "SIGWINCH"
+      ]
+    );
+    default = [ ];
+    example = lib.literalExpression ''[ "SIGHUP" ]'';
+  };
+}
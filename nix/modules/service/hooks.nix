@@ -0,0 +1,50 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+
+  step = import ../step.nix { inherit lib; };
+
+  mkHook =
+    description:
+    mkOption {
+      description = ''
+        ${description}
+
+        They run in the same environment as the service process, with its
+        `process.environment`, `process.clearEnvironment` and `XDG_CONFIG_HOME`.
+      '';
+      type = types.listOf (types.submodule { inherit (step) options; });
+      default = [ ];
+    };
+in
+{
+  options.hooks = {
+    preStart = mkHook ''
+      Commands run in order before every start of the service process,
+      mirroring systemd's `ExecStartPre=`.
+
+      If one of them fails the start counts as failed and is retried
+      according to the restart policy.
+    '';
+    postStart = mkHook ''
+      Commands run in order once the service is ready, mirroring systemd's
+      `ExecStartPost=`. Dependents only start after they succeeded.
+
+      The service keeps being watched while they run, so it exiting or
+      missing its watchdog stops them. If one of them fails the service is
+      stopped and the start counts as failed.
+    '';
+    preStop = mkHook ''
+      Commands run in order before nimi stops the service process, e.g. to
+      drain connections. Failures are logged and the process is stopped
+      anyway.
+    '';
+    postStop = mkHook ''
+      Commands run in order after the service process exited, mirroring
+      systemd's `ExecStopPost=`. Failures are logged.
+
+      `$EXIT_CODE` is set to `exited`, `killed` or `dumped`, and
+      `$EXIT_STATUS` to the exit code or the name of the signal (e.g. `TERM`).
+    '';
+  };
+}
@@ -0,0 +1,54 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+
+  probe = import ../probe.nix { inherit lib; };
+in
+{
+  options.liveness = mkOption {
+    description = ''
+      Liveness health check for the service.
+
+      Once the service is ready, nimi periodically runs the check. When it
+      fails `failureThreshold` times in a row the service is considered hung:
+      it is stopped and then restarted according to the restart policy, just
+      as if it had exited with an error.
+
+      This catches processes which stop responding without ever exiting.
+
+      Set to `null` to disable.
+    '';
+    example = lib.literalExpression ''
+      {
+        type = "http";
+        url = "http://127.0.0.1:8080/health";
+        interval = 10000;
+        failureThreshold = 3;
+      }
+    '';
+    type = types.nullOr (
+      types.submodule {
+        options = probe.options // {
+          interval = mkOption {
+            description = ''
+              Time in milliseconds to wait between checks.
+            '';
+            type = types.ints.positive;
+            default = 10000;
+            example = lib.literalExpression "5000";
+          };
+          failureThreshold = mkOption {
+            description = ''
+              Number of consecutive failed checks after which the service is
+              restarted.
+            '';
+            type = types.ints.positive;
+            default = 3;
+            example = lib.literalExpression "5";
+          };
+        };
+      }
+    );
+    default = null;
+  };
+}
@@ -0,0 +1,92 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+
+  mkLimit =
+    what: systemd: example:
+    mkOption {
+      description = ''
+        Maximum ${what}, mirroring systemd's `${systemd}=`.
+
+        Applied as both the soft and the hard limit. Raising the hard limit
+        above the one of nimi needs root. Set to `null` to inherit the limit
+        of nimi.
+      '';
+      type = types.nullOr (types.either types.ints.unsigned (types.enum [ "infinity" ]));
+      default = null;
+      example = lib.literalExpression example;
+    };
+in
+{
+  options.process = {
+    limits = {
+      nofile = mkLimit "number of open file descriptors" "LimitNOFILE" "65536";
+      core = mkLimit "size of core dumps in bytes" "LimitCORE" ''"infinity"'';
+      nproc = mkLimit "number of processes of the user running the service" "LimitNPROC" "4096";
+      as = mkLimit "size of the virtual memory of each process in bytes" "LimitAS" "4294967296";
+    };
+    nice = mkOption {
+      description = ''
+        Scheduling priority of the service, mirroring systemd's `Nice=`,
+        from -20 (highest) to 19 (lowest). Negative values need root.
+
+        Set to `null` to inherit the priority of nimi.
+      '';
+      type = types.nullOr (types.ints.between (-20) 19);
+      default = null;
+      example = lib.literalExpression "10";
+    };
+    ioSchedulingClass = mkOption {
+      description = ''
+        I/O scheduling class of the service, mirroring systemd's
+        `IOSchedulingClass=`. `realtime` needs root, and `idle` only gets disk
+        access when no other process needs it.
+
+        Set to `null` to inherit the class of nimi, or `best-effort` when
+        `ioSchedulingPriority` is set.
+      '';
+      type = types.nullOr (
+        types.enum [
+          "realtime"
+          "best-effort"
+          "idle"
+        ]
+      );
+      default = null;
+      example = lib.literalExpression ''"idle"'';
+    };
+    ioSchedulingPriority = mkOption {
+      description = ''
+        I/O scheduling priority of the service within its class, mirroring
+        systemd's `IOSchedulingPriority=`, from 0 (highest) to 7 (lowest).
+        Defaults to 4 when only `ioSchedulingClass` is set.
+      '';
+      type = types.nullOr (types.ints.between 0 7);
+      default = null;
+      example = lib.literalExpression "7";
+    };
+    oomScoreAdjust = mkOption {
+      description = ''
+        Adjustment of the score the OOM killer picks processes by, mirroring
+        systemd's `OOMScoreAdjust=`, from -1000 (never kill) to 1000 (kill
+        first). Lowering it below the one of nimi needs root.
+
+        Set to `null` to inherit the adjustment of nimi.
+      '';
+      type = types.nullOr (types.ints.between (-1000) 1000);
+      default = null;
+      example = lib.literalExpression "500";
+    };
+    umask = mkOption {
+      description = ''
+        File mode creation mask of the service in octal, mirroring systemd's
+        `UMask=`.
+
+        Set to `null` to inherit the umask of nimi.
+      '';
+      type = types.nullOr (types.strMatching "0?[0-7]{3}");
+      default = null;
+      example = lib.literalExpression ''"0027"'';
+    };
+  };
+}
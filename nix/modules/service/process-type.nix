@@ -0,0 +1,48 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+in
+{
+  options.process = {
+    type = mkOption {
+      description = ''
+        How the service reports that it has started, mirroring systemd's
+        `Type=`.
+
+        - `simple`: the service is started as soon as its process is spawned
+          (or its `readiness` probe succeeds).
+        - `notify`: the service is started once it sends `READY=1` over the
+          socket in `$NOTIFY_SOCKET`, using the
+          [`sd_notify`](https://www.freedesktop.org/software/systemd/man/latest/sd_notify.html)
+          protocol. This lets services written for systemd's `Type=notify`
+          run unchanged.
+        - `oneshot`: the service runs to completion, e.g. a database
+          migration. It is started once its process exits successfully, so
+          dependents only start after it finished, and it is never restarted
+          after succeeding.
+      '';
+      type = types.enum [
+        "simple"
+        "notify"
+        "oneshot"
+      ];
+      default = "simple";
+      example = lib.literalExpression ''"notify"'';
+    };
+    watchdog = mkOption {
+      description = ''
+        Watchdog timeout in milliseconds, mirroring systemd's `WatchdogSec=`.
+
+        The service is told the timeout through `$WATCHDOG_USEC` and has to
+        send `WATCHDOG=1` over `$NOTIFY_SOCKET` at least this often once it
+        is started. When it misses the deadline (or sends `WATCHDOG=trigger`)
+        it is stopped and restarted according to the restart policy.
+
+        Set to `null` to disable.
+      '';
+      type = types.nullOr types.ints.positive;
+      default = null;
+      example = lib.literalExpression "30000";
+    };
+  };
+}
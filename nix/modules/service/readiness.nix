@@ -0,0 +1,58 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+
+  probe = import ../probe.nix { inherit lib; };
+in
+{
+  options.readiness = mkOption {
+    description = ''
+      Readiness probe for the service.
+
+      Without a probe a service counts as ready as soon as its process has
+      been spawned. With a probe, nimi repeatedly runs the check after the
+      process starts and only reports the service as ready once it succeeds.
+
+      Services listing this one in `after` or `requires` wait for it to be
+      ready before they are started. Once the check failed `failureThreshold`
+      times in a row, the service is stopped and restarted according to the
+      restart policy, just as if it had exited with an error.
+
+      Set to `null` to disable.
+    '';
+    example = lib.literalExpression ''
+      {
+        type = "tcp";
+        address = "127.0.0.1:5432";
+        interval = 250;
+      }
+    '';
+    type = types.nullOr (
+      types.submodule {
+        options = probe.options // {
+          interval = mkOption {
+            description = ''
+              Time in milliseconds to wait between failed checks.
+            '';
+            type = types.ints.positive;
+            default = 500;
+            example = lib.literalExpression "1000";
+          };
+          failureThreshold = mkOption {
+            description = ''
+              Number of consecutive failed checks after which the service failed
+              to start. The default gives up after about 90 seconds, like
+              systemd's `TimeoutStartSec=`.
+
+              Set to `null` to keep checking forever.
+            '';
+            type = types.nullOr types.ints.positive;
+            default = 180;
+            example = lib.literalExpression "20";
+          };
+        };
+      }
+    );
+    default = null;
+  };
+}
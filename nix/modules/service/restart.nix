@@ -0,0 +1,86 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+
+  restartLimit = import ../restart-limit.nix { inherit lib; };
+in
+{
+  options.restart = mkOption {
+    description = ''
+      Per service overrides for `settings.restart`.
+
+      Every option left at `null` falls back to the global restart settings,
+      so only the differences need to be spelled out. This lets a one-off
+      migration job never restart while a web server in the same
+      configuration always does.
+    '';
+    example = lib.literalExpression ''
+      {
+        mode = "never";
+      }
+    '';
+    type = types.submodule {
+      options = {
+        mode = mkOption {
+          description = ''
+            Overrides `settings.restart.mode` for this service.
+          '';
+          type = types.nullOr (
+            types.enum [
+              "never"
+              "up-to-count"
+              "always"
+            ]
+          );
+          default = null;
+          example = lib.literalExpression ''"never"'';
+        };
+        condition = mkOption {
+          description = ''
+            Overrides `settings.restart.condition` for this service.
+          '';
+          type = types.nullOr (
+            types.enum [
+              "always"
+              "on-success"
+              "on-failure"
+              "on-abnormal"
+            ]
+          );
+          default = null;
+          example = lib.literalExpression ''"always"'';
+        };
+        time = mkOption {
+          description = ''
+            Overrides `settings.restart.time` for this service.
+          '';
+          type = types.nullOr types.ints.positive;
+          default = null;
+          example = lib.literalExpression "250";
+        };
+        count = mkOption {
+          description = ''
+            Overrides `settings.restart.count` for this service.
+          '';
+          type = types.nullOr types.ints.positive;
+          default = null;
+          example = lib.literalExpression "3";
+        };
+        limit = mkOption {
+          description = ''
+            Overrides `settings.restart.limit` for this service.
+          '';
+          type = types.nullOr (types.submodule { inherit (restartLimit) options; });
+          default = null;
+          example = lib.literalExpression ''
+            {
+              burst = 3;
+              interval = 60000;
+            }
+          '';
+        };
+      };
+    };
+    default = { };
+  };
+}
@@ -0,0 +1,28 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+
+  signal = import ../signal.nix { inherit lib; };
+in
+{
+  options.stopSignals = mkOption {
+    description = ''
+      Signals sent in order to stop the service, mirroring systemd's
+      `KillSignal=`. They are sent to the whole process group of the service,
+      so forked workers are stopped as well.
+
+      After each signal nimi waits up to `stopTimeout` for every process to
+      exit before sending the next one, and finally kills them with `SIGKILL`,
+      so stopping can take up to one `stopTimeout` per signal.
+      Use e.g. `SIGINT` for a fast postgres shutdown or `SIGQUIT` for a
+      graceful nginx shutdown. For services which are stopped with a command
+      instead, use `hooks.preStop`.
+
+      The `--tui` frontend only sends the first signal, and only supports
+      `SIGINT`, `SIGTERM` and `SIGKILL`.
+    '';
+    type = types.listOf signal;
+    default = [ "SIGTERM" ];
+    example = lib.literalExpression ''[ "SIGQUIT" "SIGTERM" ]'';
+  };
+}
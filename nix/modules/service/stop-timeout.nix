@@ -0,0 +1,18 @@
+{ lib, ... }:
+let
+  inherit (lib) mkOption types;
+in
+{
+  options.stopTimeout = mkOption {
+    description = ''
+      Overrides `settings.stopTimeout` for this service, e.g. to give a
+      database enough time to flush its data on shutdown.
+
+      Like the global setting it applies after each of `stopSignals`, and it
+      also applies to the `hooks` of this service.
+    '';
+    type = types.nullOr types.ints.positive;
+    default = null;
+    example = lib.literalExpression "60000";
+  };
+}
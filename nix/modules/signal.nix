@@ -0,0 +1,33 @@
+# Type of a signal name, limited to the signals nimi knows on every platform
+{ lib }:
+lib.types.enum [
+  "SIGHUP"
+  "SIGINT"
+  "SIGQUIT"
+  "SIGILL"
+  "SIGTRAP"
+  "SIGABRT"
+  "SIGBUS"
+  "SIGFPE"
+  "SIGKILL"
+  "SIGUSR1"
+  "SIGSEGV"
+  "SIGUSR2"
+  "SIGPIPE"
+  "SIGALRM"
+  "SIGTERM"
+  "SIGCHLD"
+  "SIGCONT"
+  "SIGSTOP"
+  "SIGTSTP"
+  "SIGTTIN"
+  "SIGTTOU"
+  "SIGURG"
+  "SIGXCPU"
+  "SIGXFSZ"
+  "SIGVTALRM"
+  "SIGPROF"
+  "SIGWINCH"
+  "SIGIO"
+  "SIGSYS"
+]
@@ -0,0 +1,50 @@
+# Options shared by every kind of step (startup steps, hooks, ...)
+{ lib }:
+let
+  inherit (lib) mkOption types;
+in
+{
+  options = {
+    argv = mkOption {
+      description = ''
+        Command to run, as a list of the binary followed by its arguments.
+      '';
+      type = types.addCheck (types.listOf types.str) (argv: argv != [ ]) // {
+        description = "non-empty list of strings";
+      };
+      example = lib.literalExpression ''[ (lib.getExe my-migrations) "--apply" ]'';
+    };
+    timeout = mkOption {
+      description = ''
+        Time in milliseconds a single attempt may take before it is stopped
+        and counted as failed.
+
+        Set to `null` to wait indefinitely.
+      '';
+      type = types.nullOr types.ints.positive;
+      default = null;
+      example = lib.literalExpression "60000";
+    };
+    retries = mkOption {
+      description = ''
+        Number of times to retry the command after a failed attempt.
+      '';
+      type = types.ints.unsigned;
+      default = 0;
+      example = lib.literalExpression "3";
+    };
+    environment = mkOption {
+      description = ''
+        Environment variables to set for the command, on top of the
+        environment nimi was started with.
+      '';
+      type = types.lazyAttrsOf types.str;
+      default = { };
+      example = lib.literalExpression ''
+        {
+          DATABASE_URL = "postgres://localhost/app";
+        }
+      '';
+    };
+  };
+}
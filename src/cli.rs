@@ -1,14 +1,21 @@
 //! Module containing the schema for the command line interface and methods to run it
 
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
 use clap::{Parser, Subcommand};
 use eyre::{Context, Result};
 use format_serde_error::SerdeError;
-use log::info;
+use log::{error, info};
 use tokio::fs;
 
-use crate::{config::Config, process_manager::ProcessManager};
+use crate::{
+    config::Config,
+    init::Init,
+    process_manager::{ProcessManager, service::exit_code, service_manager::ServiceError},
+};
 
 /// NixOS modular services runner and container init
 ///
@@ -79,32 +86,65 @@ impl Cli {
     /// Execute the nimi CLI
     ///
     /// Read the configuration file and runs the specificed `Command`
-    pub async fn run(self) -> Result<()> {
+    ///
+    /// Returns the exit code of the service which caused nimi to shut down,
+    /// `128 + signal` if it was killed by a signal
+    pub async fn run(self) -> Result<ExitCode> {
         let config = Self::read_config(&self.config)
             .await
             .wrap_err_with(|| format!("Failed to read nimi config ({:?})", self.config))?;
 
+        config
+            .validate()
+            .wrap_err_with(|| format!("Failed to validate nimi config ({:?})", self.config))?;
+
         match self.command {
             Command::Validate => {
                 info!("Successfully validated nimi config ({:?})", self.config);
 
-                Ok(())
+                Ok(ExitCode::SUCCESS)
             }
             Command::Run { tui } => {
                 info!("Launching process manager...");
 
+                let exit = config.settings.exit.clone();
+                let stop_timeout = config.settings.stop_timeout;
                 let proc_man = ProcessManager::new(config.services, config.settings);
 
-                if tui {
-                    proc_man.run_mprocs().await
+                let result = if tui {
+                    proc_man.run_mprocs().await.map(|()| None)
                 } else {
                     proc_man.run().await
+                };
+
+                // As init nimi is responsible for everything else in the container too
+                if Init::is_pid1() {
+                    Init::stop_remaining(stop_timeout).await;
                 }
-                .wrap_err("Failed to run processes")?;
+
+                let code = match result {
+                    Ok(status) => status.map_or(0, exit_code),
+                    Err(e) => {
+                        let Some(status) = e
+                            .downcast_ref::<ServiceError>()
+                            .and_then(ServiceError::exit_status)
+                        else {
+                            return Err(e).wrap_err("Failed to run processes");
+                        };
+
+                        error!("{e} ({status})");
+                        exit_code(status)
+                    }
+                };
 
                 info!("Process manager finished");
 
-                Ok(())
+                let remapped = exit.remap(code);
+                if remapped != code {
+                    info!("Remapped exit code {code} to {remapped}");
+                }
+
+                Ok(ExitCode::from(remapped))
             }
         }
     }
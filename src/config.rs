@@ -3,9 +3,10 @@
 
 use std::collections::HashMap;
 
+use eyre::{Context, Result};
 use serde::{Deserialize, Serialize};
 
-use crate::process_manager::{Service, Settings};
+use crate::process_manager::{DependencyGraph, Service, Settings};
 
 #[derive(Debug, Serialize, Deserialize)]
 /// Representation of the nimi config generated by evaluating a nimi services module
@@ -18,3 +19,42 @@ pub struct Config {
     /// Process manager settings
     pub settings: Settings,
 }
+
+impl Config {
+    /// Validate the parts of the config which can't be checked during deserialization
+    pub fn validate(&self) -> Result<()> {
+        DependencyGraph::new(&self.services)
+            .startup_order()
+            .wrap_err("Failed to resolve service dependencies")?;
+
+        for (name, service) in &self.services {
+            // Names end up in the paths of log files and cgroups
+            eyre::ensure!(
+                !matches!(name.as_str(), "" | "." | "..") && !name.contains('/'),
+                "Service name {name:?} can't be empty, `.`, `..` or contain `/`"
+            );
+
+            if let Some(signal) = service
+                .forward_signals
+                .iter()
+                .find(|signal| !Service::FORWARDABLE_SIGNALS.contains(signal))
+            {
+                eyre::bail!("Service {name:?} can't forward {signal}");
+            }
+
+            eyre::ensure!(
+                self.settings.cgroups.enable || !service.process.has_cgroup_limits(),
+                "Service {name:?} sets cgroup limits, which need settings.cgroups.enable"
+            );
+        }
+
+        if let Some(code_from) = &self.settings.exit.code_from {
+            eyre::ensure!(
+                self.services.contains_key(code_from),
+                "settings.exit.codeFrom refers to unknown service {code_from:?}"
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+//! Init mode, for when nimi runs as PID 1 of a container
+//!
+//! The kernel doesn't apply default signal dispositions to PID 1, so signals
+//! without a handler are silently dropped instead of terminating nimi. Orphans
+//! of the whole container are reparented to it as well.
+
+use std::time::Duration;
+
+use log::{debug, info};
+use nix::sys::signal::{Signal, kill};
+use nix::unistd::Pid;
+
+use crate::subreaper::Subreaper;
+
+/// Signals which terminate a process by default
+///
+/// `SIGPIPE` is left out as the Rust runtime ignores it, and signals raised by
+/// faults (e.g. `SIGSEGV`) can't be handled.
+const TERMINATING_SIGNALS: [Signal; 12] = [
+    Signal::SIGHUP,
+    Signal::SIGINT,
+    Signal::SIGQUIT,
+    Signal::SIGTERM,
+    Signal::SIGUSR1,
+    Signal::SIGUSR2,
+    Signal::SIGALRM,
+    Signal::SIGIO,
+    Signal::SIGPROF,
+    Signal::SIGVTALRM,
+    Signal::SIGXCPU,
+    Signal::SIGXFSZ,
+];
+
+/// Init mode detection and behavior
+pub struct Init;
+
+impl Init {
+    /// Check if nimi runs as PID 1
+    pub fn is_pid1() -> bool {
+        std::process::id() == 1
+    }
+
+    /// Raw signal numbers which should shut nimi down gracefully
+    ///
+    /// In init mode this is every signal which terminates by default, including
+    /// real time signals (`SIGRTMIN+3` stops systemd based images). Otherwise
+    /// it's `SIGINT` and `SIGTERM`, leaving the rest at their default behavior.
+    pub fn shutdown_signals() -> Vec<i32> {
+        if !Self::is_pid1() {
+            return vec![Signal::SIGINT as i32, Signal::SIGTERM as i32];
+        }
+
+        let signals = TERMINATING_SIGNALS.iter().map(|&signal| signal as i32);
+
+        #[cfg(target_os = "linux")]
+        let signals = signals
+            .chain([Signal::SIGPWR as i32])
+            .chain(libc::SIGRTMIN()..=libc::SIGRTMAX());
+
+        signals.collect()
+    }
+
+    /// Get a printable name for a raw signal number
+    pub fn signal_name(signal: i32) -> String {
+        match Signal::try_from(signal) {
+            Ok(signal) => signal.to_string(),
+            #[cfg(target_os = "linux")]
+            Err(_) => format!("SIGRTMIN+{}", signal - libc::SIGRTMIN()),
+            #[cfg(not(target_os = "linux"))]
+            Err(_) => signal.to_string(),
+        }
+    }
+
+    /// Stop every process left in the container
+    ///
+    /// Sends `SIGTERM` to all remaining processes, waits up to `timeout` for
+    /// them to exit and be reaped, and kills the rest with `SIGKILL`.
+    pub async fn stop_remaining(timeout: Duration) {
+        if !Subreaper::has_children() {
+            return;
+        }
+
+        info!("Stopping remaining processes...");
+        let _ = kill(Pid::from_raw(-1), Signal::SIGTERM);
+        if tokio::time::timeout(timeout, Self::wait_for_children())
+            .await
+            .is_ok()
+        {
+            return;
+        }
+
+        debug!("Killing remaining processes");
+        let _ = kill(Pid::from_raw(-1), Signal::SIGKILL);
+        Self::wait_for_children().await;
+    }
+
+    /// Wait until the subreaper reaped every child
+    async fn wait_for_children() {
+        while Subreaper::has_children() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
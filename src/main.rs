@@ -4,9 +4,12 @@
 
 pub mod cli;
 pub mod config;
+pub mod init;
 pub mod process_manager;
 pub mod subreaper;
 
+use std::process::ExitCode;
+
 use clap::Parser;
 use env_logger::Env;
 use eyre::{Context, Result};
@@ -14,7 +17,7 @@ use eyre::{Context, Result};
 use crate::{cli::Cli, subreaper::Subreaper};
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     color_eyre::install().wrap_err("Failed to setup color_eyre")?;
     env_logger::Builder::from_env(Env::default().default_filter_or("debug"))
         .try_init()
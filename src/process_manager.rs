@@ -4,27 +4,38 @@
 //! and runs them streaming logs back to the original console.
 
 use eyre::{Context, Result};
-use futures::future::OptionFuture;
+use futures::future::{OptionFuture, select_all};
 use libmprocs::{ProcConfig, StopSignal, mprocs};
-use log::{debug, info};
-use std::process::Stdio;
+use log::{error, info, warn};
+use nix::sys::signal::Signal;
+use std::process::ExitStatus;
+use std::time::Duration;
 use std::{collections::HashMap, env, io::ErrorKind, path::PathBuf, sync::Arc};
 use tokio::signal::unix::{SignalKind, signal};
-use tokio::{fs, process::Command, task::JoinSet};
+use tokio::{
+    fs,
+    sync::{broadcast, watch},
+    task::JoinSet,
+};
 use tokio_util::sync::CancellationToken;
 
+pub mod dependency_graph;
 pub mod service;
 pub mod service_manager;
 pub mod settings;
+pub mod step;
 
+pub use dependency_graph::DependencyGraph;
 pub use service::Service;
 pub use service_manager::ServiceManager;
 pub use settings::Settings;
+pub use step::{Step, StepEnv};
 
+use crate::init::Init;
+use crate::process_manager::service::ProcessType;
 use crate::process_manager::service_manager::{
-    ConfigDir, Logger, ServiceError, ServiceManagerOpts,
+    Cgroup, ConfigDir, Dependency, NotifySocket, ServiceManagerOpts, ServiceOutcome, ServiceState,
 };
-use crate::subreaper::Subreaper;
 
 /// Process Manager Struct
 ///
@@ -40,50 +51,101 @@ impl ProcessManager {
         Self { services, settings }
     }
 
-    async fn run_startup_process(&self, bin: &str, cancel_tok: &CancellationToken) -> Result<()> {
-        let mut set = JoinSet::new();
-
-        let _pause = Subreaper::pause_reaping();
-        let mut process = Command::new(bin)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .kill_on_drop(true)
-            .spawn()
-            .wrap_err_with(|| format!("Failed to spawn startup binary: {:?}", bin))?;
-        let _child_guard =
-            Subreaper::track_child(process.id()).wrap_err("Failed to track startup child")?;
-
-        let name = Arc::new("startup".to_owned());
-        let logs_dir = Arc::from(None);
-
-        Logger::Stdout.start(
-            &mut process.stdout,
-            Arc::clone(&name),
-            Arc::clone(&logs_dir),
-            &mut set,
-        )?;
-        Logger::Stderr.start(
-            &mut process.stderr,
-            Arc::clone(&name),
-            Arc::clone(&logs_dir),
-            &mut set,
-        )?;
-
-        tokio::select! {
-            _ = cancel_tok.cancelled() => {
-                debug!(target: &name, "Received shutdown signal");
-                ServiceManager::shutdown_process(&mut process, self.settings.restart.time).await?;
+    /// Run the startup binary and steps in order
+    ///
+    /// Stops early if the process manager gets shut down in the meantime
+    async fn run_startup(
+        &self,
+        logs_dir: &Arc<Option<PathBuf>>,
+        cancel_tok: &CancellationToken,
+    ) -> Result<()> {
+        let stop_timeout = self.settings.stop_timeout;
+
+        if let Some(startup) = &self.settings.startup.run_on_startup {
+            info!("Running startup binary ({})...", startup);
+            Step::from_binary(startup)?
+                .run(
+                    &Arc::new("startup".to_owned()),
+                    logs_dir,
+                    cancel_tok,
+                    stop_timeout,
+                    &StepEnv::default(),
+                )
+                .await
+                .wrap_err("Failed to run startup process")?;
+        }
+
+        for (idx, step) in self.settings.startup.steps.iter().enumerate() {
+            if cancel_tok.is_cancelled() {
+                break;
             }
-            status = process.wait() => {
-                let status = status.wrap_err("Failed to get process status")?;
-                eyre::ensure!(
-                    status.success(),
-                    ServiceError::ProcessExited { status }
-                );
+
+            let number = idx + 1;
+            info!("Running startup step {number} ({})...", step.argv.binary());
+            step.run(
+                &Arc::new(format!("startup-{number}")),
+                logs_dir,
+                cancel_tok,
+                stop_timeout,
+                &StepEnv::default(),
+            )
+            .await
+            .wrap_err_with(|| format!("Failed to run startup step {number}"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Run the shutdown steps in order
+    ///
+    /// Every step runs even if an earlier one failed, so e.g. a failed cache
+    /// flush doesn't prevent uploading artifacts. Fails if any step failed.
+    async fn run_shutdown(
+        steps: &[Step],
+        logs_dir: &Arc<Option<PathBuf>>,
+        stop_timeout: Duration,
+    ) -> Result<()> {
+        let mut result = Ok(());
+
+        for (idx, step) in steps.iter().enumerate() {
+            let number = idx + 1;
+            info!("Running shutdown step {number} ({})...", step.argv.binary());
+
+            // Services are already stopped, so the steps only end on their own or their timeout
+            let step_result = step
+                .run(
+                    &Arc::new(format!("shutdown-{number}")),
+                    logs_dir,
+                    &CancellationToken::new(),
+                    stop_timeout,
+                    &StepEnv::default(),
+                )
+                .await
+                .wrap_err_with(|| format!("Failed to run shutdown step {number}"));
+
+            match step_result {
+                Err(e) if result.is_ok() => result = Err(e),
+                Err(e) => error!("{e:?}"),
+                Ok(()) => {}
             }
         }
 
-        set.join_all().await.into_iter().collect()
+        result
+    }
+
+    /// Create the logs directory, if logging to files is enabled
+    async fn logs_dir(&self) -> Result<Arc<Option<PathBuf>>> {
+        let logs_dir = OptionFuture::from(
+            self.settings
+                .logging
+                .logs_dir
+                .as_deref()
+                .map(Self::create_logs_dir),
+        )
+        .await
+        .transpose()?;
+
+        Ok(Arc::new(logs_dir))
     }
 
     /// Create logs dir
@@ -114,109 +176,319 @@ impl ProcessManager {
 
     /// Spawn Child Processes
     ///
-    /// Spawns every service this process manager manages into a `JoinSet`
+    /// Spawns every service this process manager manages into a `JoinSet`,
+    /// in the order of their dependencies
     pub async fn spawn_child_processes(
-        self,
+        mut self,
+        logs_dir: Arc<Option<PathBuf>>,
         cancel_tok: &CancellationToken,
-    ) -> Result<JoinSet<Result<()>>> {
+    ) -> Result<JoinSet<(Arc<String>, Result<ServiceOutcome>)>> {
         let mut join_set = tokio::task::JoinSet::new();
 
+        let graph = DependencyGraph::new(&self.services);
+        let order: Vec<_> = graph
+            .startup_order()
+            .wrap_err("Failed to resolve service dependencies")?
+            .into_iter()
+            .map(str::to_owned)
+            .collect();
+
+        let names: HashMap<_, _> = order
+            .iter()
+            .map(|name| (name.clone(), Arc::new(name.clone())))
+            .collect();
+        let (mut senders, receivers): (HashMap<_, _>, HashMap<_, _>) = order
+            .iter()
+            .map(|name| {
+                let (sender, receiver) = watch::channel(ServiceState::Pending);
+                ((name.clone(), sender), (name.clone(), receiver))
+            })
+            .unzip();
+        let mut states: Vec<_> = receivers.values().cloned().collect();
+        tokio::spawn(async move {
+            if ServiceState::wait_for_ready(&mut states).await {
+                info!("All services are ready");
+            } else {
+                warn!("Startup finished, but not every service became ready");
+            }
+
+            if let Err(e) = NotifySocket::notify_supervisor("READY=1") {
+                warn!("{e:?}");
+            }
+        });
+
+        let mut dependents: HashMap<_, Vec<_>> = order
+            .iter()
+            .map(|name| {
+                let states = graph
+                    .dependents(name)
+                    .map(|dependent| receivers[dependent].clone())
+                    .collect();
+                (name.clone(), states)
+            })
+            .collect();
+
+        let signals = self.spawn_signal_forwarding()?;
+
+        let cgroup_root = self
+            .settings
+            .cgroups
+            .enable
+            .then(Cgroup::setup_root)
+            .transpose()
+            .wrap_err("Failed to set up cgroups")?
+            .map(Arc::new);
+
         let settings = Arc::new(self.settings);
-        let logs_dir = Arc::new(
-            OptionFuture::from(
-                settings
-                    .logging
-                    .logs_dir
-                    .as_deref()
-                    .map(Self::create_logs_dir),
-            )
-            .await
-            .transpose()?,
-        );
         let tmp_dir = Arc::new(env::temp_dir());
 
-        for (name, service) in self.services {
+        for name in order {
+            let Some(service) = self.services.remove(&name) else {
+                continue;
+            };
+
+            let dependencies = service
+                .dependencies()
+                .into_iter()
+                .map(|dependency| Dependency {
+                    name: Arc::clone(&names[dependency]),
+                    required: service.requires.iter().any(|r| r == dependency),
+                    state: receivers[dependency].clone(),
+                })
+                .collect();
+
+            let Some(state) = senders.remove(&name) else {
+                continue;
+            };
+
             let opts = ServiceManagerOpts {
                 logs_dir: Arc::clone(&logs_dir),
                 tmp_dir: Arc::clone(&tmp_dir),
+                cgroup_root: cgroup_root.clone(),
 
                 settings: Arc::clone(&settings),
 
-                name: Arc::new(name),
+                name: Arc::clone(&names[&name]),
                 service,
                 cancel_tok: cancel_tok.clone(),
+
+                state,
+                dependencies,
+                dependents: dependents.remove(&name).unwrap_or_default(),
+                signals: signals.subscribe(),
             };
 
-            join_set.spawn(async move { ServiceManager::new(opts).await?.run().await });
+            let name = Arc::clone(&opts.name);
+            join_set.spawn(async move {
+                let result = async { ServiceManager::new(opts).await?.run().await }.await;
+                (name, result)
+            });
         }
 
         Ok(join_set)
     }
 
-    fn spawn_shutdown_task(&self, cancel_tok: &CancellationToken) {
+    /// Listen for the signals any service wants forwarded
+    ///
+    /// Each received signal is broadcast to every service manager, which
+    /// forwards it if its service asked for it. Other signals keep their
+    /// default behavior.
+    fn spawn_signal_forwarding(&self) -> Result<broadcast::Sender<Signal>> {
+        let (sender, _) = broadcast::channel(16);
+
+        for signal in Service::FORWARDABLE_SIGNALS {
+            if !self
+                .services
+                .values()
+                .any(|service| service.forward_signals.contains(&signal))
+            {
+                continue;
+            }
+
+            let mut stream = tokio::signal::unix::signal(SignalKind::from_raw(signal as i32))
+                .wrap_err_with(|| format!("Failed to register {signal} handler"))?;
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                while stream.recv().await.is_some() {
+                    info!("Received {signal}, forwarding to services...");
+                    let _ = sender.send(signal);
+                }
+            });
+        }
+
+        Ok(sender)
+    }
+
+    /// Cancel `cancel_tok` once nimi receives a signal asking it to shut down
+    ///
+    /// Signals which any service wants forwarded are left to the forwarding
+    fn spawn_shutdown_task(&self, cancel_tok: &CancellationToken) -> Result<()> {
+        let forwarded: Vec<_> = self
+            .services
+            .values()
+            .flat_map(|service| &service.forward_signals)
+            .map(|&signal| signal as i32)
+            .collect();
+
+        let mut streams = Vec::new();
+        for raw in Init::shutdown_signals() {
+            if forwarded.contains(&raw) {
+                continue;
+            }
+
+            let stream = signal(SignalKind::from_raw(raw)).wrap_err_with(|| {
+                format!("Failed to register {} handler", Init::signal_name(raw))
+            })?;
+            streams.push((raw, stream));
+        }
+
         let token = cancel_tok.clone();
         tokio::spawn(async move {
-            let mut sigterm =
-                signal(SignalKind::terminate()).wrap_err("Failed to register SIGTERM handler")?;
-            tokio::select! {
-                _ = tokio::signal::ctrl_c() => {},
-                _ = sigterm.recv() => {},
+            let received = streams
+                .iter_mut()
+                .map(|(raw, stream)| Box::pin(async move { stream.recv().await.map(|()| *raw) }));
+            if let (Some(raw), ..) = select_all(received).await {
+                info!("Received {}, shutting down...", Init::signal_name(raw));
             }
             token.cancel();
-            Ok::<_, eyre::Report>(())
         });
+
+        Ok(())
     }
 
     /// Run the services defined for the process manager instance
     ///
-    /// Terminates on `Ctrl-C`
-    pub async fn run(self) -> Result<()> {
+    /// Terminates on `Ctrl-C`, or once a service exits when `settings.exit`
+    /// asks for it. Returns the exit status nimi should report, `None` for a
+    /// graceful shutdown.
+    pub async fn run(mut self) -> Result<Option<ExitStatus>> {
         info!("Starting process manager...");
 
         let cancel_tok = CancellationToken::new();
-        self.spawn_shutdown_task(&cancel_tok);
+        self.spawn_shutdown_task(&cancel_tok)?;
 
-        if let Some(startup) = &self.settings.startup.run_on_startup {
-            info!("Running startup binary ({})...", startup);
-            self.run_startup_process(startup, &cancel_tok)
-                .await
-                .wrap_err("Failed to run startup process")?;
-        }
+        let logs_dir = self.logs_dir().await?;
+        let shutdown_steps = std::mem::take(&mut self.settings.shutdown.steps);
+        let stop_timeout = self.settings.stop_timeout;
 
-        let mut services_set = self.spawn_child_processes(&cancel_tok).await?;
+        let result = self.run_services(&logs_dir, &cancel_tok).await;
 
-        while let Some(res) = services_set.join_next().await {
-            let flat: Result<()> = res.map_err(Into::into).and_then(std::convert::identity);
+        info!("Shutting down process manager...");
+        Self::finish(result, &shutdown_steps, &logs_dir, stop_timeout).await
+    }
 
-            if let Err(e) = flat {
-                cancel_tok.cancel();
-                return Err(e);
+    /// Run the startup steps and then every service until all of them stopped
+    ///
+    /// Returns the exit status nimi should report, if any
+    async fn run_services(
+        self,
+        logs_dir: &Arc<Option<PathBuf>>,
+        cancel_tok: &CancellationToken,
+    ) -> Result<Option<ExitStatus>> {
+        self.run_startup(logs_dir, cancel_tok).await?;
+
+        let exit = self.settings.exit.clone();
+        let mut services_set = self
+            .spawn_child_processes(Arc::clone(logs_dir), cancel_tok)
+            .await?;
+
+        let mut result = Ok(None);
+        let mut code_from_status = None;
+        while let Some(res) = services_set.join_next().await {
+            let flat = res
+                .map_err(Into::into)
+                .and_then(|(name, result)| result.map(|outcome| (name, outcome)));
+
+            match flat {
+                Err(e) if result.is_ok() => {
+                    info!("Stopping all services...");
+                    cancel_tok.cancel();
+                    result = Err(e);
+                }
+                Err(e) => error!("{e:?}"),
+                Ok((name, outcome)) => {
+                    let is_code_from = exit.code_from.as_deref() == Some(name.as_str());
+                    if is_code_from {
+                        code_from_status = outcome.exit_status();
+                    }
+
+                    // Completed oneshot services are expected to exit, so they don't abort
+                    let aborts = exit.abort_on_exit && outcome.state != ServiceState::Completed;
+                    if (aborts || is_code_from) && !cancel_tok.is_cancelled() {
+                        info!("Service {name} exited, stopping all services...");
+                        cancel_tok.cancel();
+                        result = Ok(outcome.exit_status());
+                    }
+                }
             }
         }
 
-        info!("Shutting down process manager...");
+        if exit.code_from.is_some() && result.is_ok() {
+            return Ok(code_from_status);
+        }
 
-        Ok(())
+        result
+    }
+
+    /// Run the shutdown steps once the services are done, whether or not
+    /// `result` is a failure
+    ///
+    /// Keeps the error of `result` over the one of the shutdown steps
+    async fn finish<T>(
+        result: Result<T>,
+        shutdown_steps: &[Step],
+        logs_dir: &Arc<Option<PathBuf>>,
+        stop_timeout: Duration,
+    ) -> Result<T> {
+        let shutdown = Self::run_shutdown(shutdown_steps, logs_dir, stop_timeout).await;
+        match shutdown {
+            Err(e) if result.is_ok() => Err(e),
+            Err(e) => {
+                error!("{e:?}");
+                result
+            }
+            Ok(()) => result,
+        }
     }
 
     /// Run the services defined for the process manager instance
     /// in mprocs
-    pub async fn run_mprocs(self) -> Result<()> {
+    pub async fn run_mprocs(mut self) -> Result<()> {
         info!("Starting process manager...");
 
         let cancel_tok = CancellationToken::new();
-        self.spawn_shutdown_task(&cancel_tok);
+        self.spawn_shutdown_task(&cancel_tok)?;
 
-        if let Some(startup) = &self.settings.startup.run_on_startup {
-            info!("Running startup binary ({})...", startup);
-            self.run_startup_process(startup, &cancel_tok)
-                .await
-                .wrap_err("Failed to run startup process")?;
+        let logs_dir = self.logs_dir().await?;
+        let shutdown_steps = std::mem::take(&mut self.settings.shutdown.steps);
+        let stop_timeout = self.settings.stop_timeout;
+
+        let result = self.run_mprocs_services(&logs_dir, &cancel_tok).await;
+
+        Self::finish(result, &shutdown_steps, &logs_dir, stop_timeout).await
+    }
+
+    /// Run the startup steps and then every service in mprocs until it is closed
+    async fn run_mprocs_services(
+        self,
+        logs_dir: &Arc<Option<PathBuf>>,
+        cancel_tok: &CancellationToken,
+    ) -> Result<()> {
+        self.run_startup(logs_dir, cancel_tok).await?;
+
+        if self.settings.cgroups.enable {
+            warn!("mprocs doesn't support cgroups, services run without their limits");
         }
 
         let tmp_dir = env::temp_dir();
 
         for (name, service) in &self.services {
+            if service.process.has_attributes() {
+                warn!(
+                    "mprocs can't apply the process attributes of {name}, like limits and priorities"
+                );
+            }
+
             ConfigDir::new(&tmp_dir, &service.config_data)
                 .await
                 .wrap_err_with(|| format!("Failed to create config dir for {}", name))?;
@@ -234,22 +506,49 @@ impl From<ProcessManager> for Vec<ProcConfig> {
         value
             .services
             .into_iter()
-            .map(|(name, service)| ProcConfig {
-                name,
-                cmd: service.process.into(),
-                cwd: std::env::current_dir().ok().map(|p| p.into_os_string()),
-                env: None,
-                autostart: true,
-                autorestart: value.settings.autorestart(),
-
-                stop: StopSignal::SIGTERM,
-
-                deps: Vec::default(),
-
-                mouse_scroll_speed: 5,
-                scrollback_len: 1000,
-                log: None,
+            .map(|(name, service)| {
+                let restart = value.settings.restart.with_overrides(&service.restart);
+                // Oneshot services run to completion, mprocs would restart them after it
+                let autorestart =
+                    restart.autorestart() && service.process.kind != ProcessType::Oneshot;
+                let stop = stop_signal(&name, &service.stop_signals);
+
+                ProcConfig {
+                    name,
+                    env: Some(service.process.mprocs_env()),
+                    deps: service
+                        .dependencies()
+                        .into_iter()
+                        .map(str::to_owned)
+                        .collect(),
+                    cmd: service.process.into(),
+                    cwd: std::env::current_dir().ok().map(|p| p.into_os_string()),
+                    autostart: true,
+                    autorestart,
+
+                    stop,
+
+                    mouse_scroll_speed: 5,
+                    scrollback_len: 1000,
+                    log: None,
+                }
             })
             .collect()
     }
 }
+
+/// Pick the signal mprocs sends to stop a service
+///
+/// mprocs only supports a single signal, so this uses the first of `signals`.
+/// An empty list maps to killing the process right away.
+fn stop_signal(name: &str, signals: &[Signal]) -> StopSignal {
+    match signals.first() {
+        None | Some(Signal::SIGKILL) => StopSignal::SIGKILL,
+        Some(Signal::SIGINT) => StopSignal::SIGINT,
+        Some(Signal::SIGTERM) => StopSignal::SIGTERM,
+        Some(signal) => {
+            warn!("mprocs can't stop {name} with {signal}, using SIGTERM instead");
+            StopSignal::SIGTERM
+        }
+    }
+}
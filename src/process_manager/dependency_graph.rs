@@ -0,0 +1,127 @@
+//! Dependency Graph Module
+//!
+//! Orders services based on the `after` and `requires` relations between them
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use thiserror::Error;
+
+use crate::process_manager::Service;
+
+/// Errors which can occur when resolving the dependency graph
+#[derive(Error, Debug)]
+pub enum DependencyError {
+    /// Error for when a service depends on a service which doesn't exist
+    #[error("Service {service:?} depends on unknown service {dependency:?}")]
+    UnknownService {
+        /// The service declaring the dependency
+        service: String,
+        /// The missing dependency
+        dependency: String,
+    },
+
+    /// Error for when services depend on each other in a cycle
+    #[error("Services have a dependency cycle: {}", cycle.join(" -> "))]
+    Cycle {
+        /// The services making up the cycle, starting and ending with the same service
+        cycle: Vec<String>,
+    },
+}
+
+/// Dependency graph of services
+///
+/// Maps each service name to the names of the services it has to be started after
+pub struct DependencyGraph<'a> {
+    dependencies: BTreeMap<&'a str, BTreeSet<&'a str>>,
+}
+
+impl<'a> DependencyGraph<'a> {
+    /// Create a dependency graph from a set of services
+    pub fn new(services: &'a HashMap<String, Service>) -> Self {
+        let dependencies = services
+            .iter()
+            .map(|(name, service)| (name.as_str(), service.dependencies()))
+            .collect();
+
+        Self { dependencies }
+    }
+
+    /// Get the names of the services which depend on `name`
+    pub fn dependents(&self, name: &str) -> impl Iterator<Item = &'a str> {
+        self.dependencies
+            .iter()
+            .filter(move |(_, dependencies)| dependencies.contains(name))
+            .map(|(dependent, _)| *dependent)
+    }
+
+    /// Resolve the order services have to be started in
+    ///
+    /// Every service comes after all of its dependencies. Services are
+    /// stopped in the reverse of this order.
+    pub fn startup_order(&self) -> Result<Vec<&'a str>, DependencyError> {
+        for (service, dependencies) in &self.dependencies {
+            if let Some(dependency) = dependencies
+                .iter()
+                .find(|dependency| !self.dependencies.contains_key(*dependency))
+            {
+                return Err(DependencyError::UnknownService {
+                    service: service.to_string(),
+                    dependency: dependency.to_string(),
+                });
+            }
+        }
+
+        let mut remaining = self.dependencies.clone();
+        let mut order = Vec::with_capacity(remaining.len());
+
+        while !remaining.is_empty() {
+            let ready: Vec<_> = remaining
+                .iter()
+                .filter(|(_, dependencies)| {
+                    dependencies
+                        .iter()
+                        .all(|dependency| !remaining.contains_key(dependency))
+                })
+                .map(|(name, _)| *name)
+                .collect();
+
+            if ready.is_empty() {
+                return Err(DependencyError::Cycle {
+                    cycle: Self::find_cycle(&remaining),
+                });
+            }
+
+            for name in ready {
+                remaining.remove(name);
+                order.push(name);
+            }
+        }
+
+        Ok(order)
+    }
+
+    /// Walk the unresolved services until one is visited twice
+    ///
+    /// Every unresolved service has at least one unresolved dependency, so
+    /// this always ends up in a cycle.
+    fn find_cycle(remaining: &BTreeMap<&'a str, BTreeSet<&'a str>>) -> Vec<String> {
+        let mut path: Vec<&str> = Vec::new();
+        let mut current = remaining.keys().next().copied();
+
+        while let Some(name) = current {
+            if let Some(start) = path.iter().position(|visited| *visited == name) {
+                let mut cycle: Vec<_> = path[start..].iter().map(|s| s.to_string()).collect();
+                cycle.push(name.to_owned());
+                return cycle;
+            }
+
+            path.push(name);
+            current = remaining[name]
+                .iter()
+                .find(|dependency| remaining.contains_key(*dependency))
+                .copied();
+        }
+
+        path.into_iter().map(str::to_owned).collect()
+    }
+}
@@ -2,18 +2,31 @@
 //!
 //! Singly handles (de)serialization of the service data to/from the nix type
 
+use std::{collections::BTreeSet, time::Duration};
+
+use nix::sys::signal::Signal;
 use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, DurationMilliSeconds, serde_as};
+
+use crate::process_manager::settings::RestartOverrides;
 
 mod config_data;
+mod exit_status;
+mod hooks;
+mod probe;
 mod process;
 
 pub use config_data::{ConfigData, ConfigDataMap};
-pub use process::{ArgV, Process};
+pub use exit_status::{ExitKind, ExitStatusSet, exit_code};
+pub use hooks::Hooks;
+pub use probe::{Liveness, Probe, Readiness};
+pub use process::{ArgV, IoSchedulingClass, Limit, Limits, Process, ProcessType, Umask};
 
 /// Service Data Struct
 ///
 /// Rust based mirror of the services as defined in the [NixOS Modular Services
 /// Modules](https://github.com/NixOS/nixpkgs/blob/a338deb8a1d11ead60c3d20b03f466b745514c38/lib/services/service.nix).
+#[serde_as]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Service {
     /// Configuration files for the service
@@ -22,4 +35,85 @@ pub struct Service {
 
     /// Process configuration
     pub process: Process,
+
+    /// Services which have to be started before this one
+    #[serde(default)]
+    pub after: Vec<String>,
+
+    /// Services which have to be started before this one, and without
+    /// which this one can't run
+    #[serde(default)]
+    pub requires: Vec<String>,
+
+    /// Probe deciding when the service is ready
+    #[serde(default)]
+    pub readiness: Option<Readiness>,
+
+    /// Health check restarting the service once it stops responding
+    #[serde(default)]
+    pub liveness: Option<Liveness>,
+
+    /// Whether a failure of this service shuts down every other service
+    #[serde(default)]
+    pub critical: bool,
+
+    /// Signals sent in order to stop the service, each followed by waiting
+    /// up to the stop timeout, before it is killed with `SIGKILL`
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(rename = "stopSignals", default = "default_stop_signals")]
+    pub stop_signals: Vec<Signal>,
+
+    /// Signals received by nimi which are forwarded to the service process
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(rename = "forwardSignals", default)]
+    pub forward_signals: Vec<Signal>,
+
+    /// Commands run around the lifecycle of the service
+    #[serde(default)]
+    pub hooks: Hooks,
+
+    /// Replaces `Settings::stop_timeout` for this service
+    #[serde_as(as = "Option<DurationMilliSeconds<u64>>")]
+    #[serde(rename = "stopTimeout", default)]
+    pub stop_timeout: Option<Duration>,
+
+    /// Overrides for the global restart settings
+    #[serde(default)]
+    pub restart: RestartOverrides,
+
+    /// Exit codes and signals which count as a clean exit, on top of code `0`
+    #[serde(rename = "successExitStatus", default)]
+    pub success_exit_status: ExitStatusSet,
+
+    /// Exit codes and signals after which the service is never restarted
+    #[serde(rename = "restartPreventExitStatus", default)]
+    pub restart_prevent_exit_status: ExitStatusSet,
+}
+
+fn default_stop_signals() -> Vec<Signal> {
+    vec![Signal::SIGTERM]
+}
+
+impl Service {
+    /// Signals which can be forwarded to services
+    ///
+    /// `SIGINT` and `SIGTERM` are left out as they stop nimi itself
+    pub const FORWARDABLE_SIGNALS: [Signal; 4] = [
+        Signal::SIGHUP,
+        Signal::SIGUSR1,
+        Signal::SIGUSR2,
+        Signal::SIGWINCH,
+    ];
+
+    /// Get the names of every service this service has to be started after
+    ///
+    /// `requires` implies `after`, so this includes both, with services listed
+    /// in both only once
+    pub fn dependencies(&self) -> BTreeSet<&str> {
+        self.after
+            .iter()
+            .chain(self.requires.iter())
+            .map(String::as_str)
+            .collect()
+    }
 }
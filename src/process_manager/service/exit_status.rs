@@ -0,0 +1,71 @@
+use std::{os::unix::process::ExitStatusExt, process::ExitStatus};
+
+use nix::sys::signal::Signal;
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+
+/// A set of exit codes and terminating signals
+#[serde_as]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExitStatusSet {
+    /// Exit codes in the set
+    #[serde(default)]
+    pub codes: Vec<i32>,
+
+    /// Terminating signals in the set, e.g. `SIGTERM`
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default)]
+    pub signals: Vec<Signal>,
+}
+
+impl ExitStatusSet {
+    /// Check if the exit status is one of the codes or signals in the set
+    pub fn contains(&self, status: ExitStatus) -> bool {
+        if let Some(code) = status.code() {
+            return self.codes.contains(&code);
+        }
+
+        status
+            .signal()
+            .and_then(|signal| Signal::try_from(signal).ok())
+            .is_some_and(|signal| self.signals.contains(&signal))
+    }
+}
+
+/// Convert an exit status into the code a shell would report for it
+///
+/// Processes killed by a signal map to `128 + signal`
+pub fn exit_code(status: ExitStatus) -> u8 {
+    let code = status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1);
+
+    u8::try_from(code & 0xff).unwrap_or(1)
+}
+
+/// How a service process ended, used to decide whether to restart it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitKind {
+    /// Exited with code `0` or one of the service's success statuses
+    Success,
+
+    /// Exited with any other exit code
+    Failure,
+
+    /// Killed by a signal, or stopped for failing its liveness probe or watchdog
+    Abnormal,
+}
+
+impl ExitKind {
+    /// Classify an exit status, treating `success` as clean exits on top of code `0`
+    pub fn classify(status: ExitStatus, success: &ExitStatusSet) -> Self {
+        if status.success() || success.contains(status) {
+            Self::Success
+        } else if status.code().is_some() {
+            Self::Failure
+        } else {
+            Self::Abnormal
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+use crate::process_manager::Step;
+
+/// Commands run around the lifecycle of a service
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Hooks {
+    /// Run before the process is spawned, failing counts as a failed start
+    #[serde(rename = "preStart", default)]
+    pub pre_start: Vec<Step>,
+
+    /// Run once the service is ready, before dependents are started
+    #[serde(rename = "postStart", default)]
+    pub post_start: Vec<Step>,
+
+    /// Run before nimi stops the process
+    #[serde(rename = "preStop", default)]
+    pub pre_stop: Vec<Step>,
+
+    /// Run after the process exited, with `EXIT_CODE` and `EXIT_STATUS` set
+    #[serde(rename = "postStop", default)]
+    pub post_stop: Vec<Step>,
+}
@@ -0,0 +1,250 @@
+use std::{path::PathBuf, process::Stdio, time::Duration};
+
+use eyre::{Context, OptionExt, Result, eyre};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serde_with::{DurationMilliSeconds, serde_as};
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    process::Command,
+    time::timeout,
+};
+
+use crate::process_manager::service::ArgV;
+use crate::subreaper::Subreaper;
+
+/// A check run against a service to see if it is up
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Probe {
+    /// Succeeds once a TCP connection is accepted
+    #[serde(rename = "tcp")]
+    Tcp {
+        /// `host:port` to connect to
+        address: String,
+    },
+
+    /// Succeeds once a `GET` request returns a `2xx` status
+    #[serde(rename = "http")]
+    Http {
+        /// Plain `http://` URL to request
+        url: String,
+    },
+
+    /// Succeeds once the command exits with code `0`
+    #[serde(rename = "exec")]
+    Exec {
+        /// Command to run
+        argv: ArgV,
+    },
+
+    /// Succeeds once the file exists
+    #[serde(rename = "file")]
+    File {
+        /// Path to check for
+        path: PathBuf,
+    },
+}
+
+impl Probe {
+    /// Run the check once, failing if it doesn't succeed within `timeout_duration`
+    pub async fn check(&self, timeout_duration: Duration) -> Result<()> {
+        timeout(timeout_duration, self.check_inner())
+            .await
+            .map_err(|_| eyre!("Probe timed out after {timeout_duration:?}"))?
+    }
+
+    async fn check_inner(&self) -> Result<()> {
+        match self {
+            Self::Tcp { address } => {
+                TcpStream::connect(address)
+                    .await
+                    .wrap_err_with(|| format!("Failed to connect to {address}"))?;
+
+                Ok(())
+            }
+            Self::Http { url } => Self::check_http(url).await,
+            Self::Exec { argv } => {
+                let (mut process, _child_guard) = {
+                    let _pause = Subreaper::pause_reaping();
+                    let process = Command::new(argv.binary())
+                        .args(argv.args())
+                        .env_remove("NOTIFY_SOCKET")
+                        .stdin(Stdio::null())
+                        .stdout(Stdio::null())
+                        .stderr(Stdio::null())
+                        .kill_on_drop(true)
+                        .spawn()
+                        .wrap_err_with(|| format!("Failed to spawn probe command: {argv:?}"))?;
+                    let guard = Subreaper::track_child(process.id())
+                        .wrap_err("Failed to track probe child")?;
+
+                    (process, guard)
+                };
+
+                let status = process
+                    .wait()
+                    .await
+                    .wrap_err("Failed to get probe status")?;
+                eyre::ensure!(status.success(), "Probe command exited with {status}");
+
+                Ok(())
+            }
+            Self::File { path } => {
+                eyre::ensure!(
+                    fs::try_exists(path).await.unwrap_or(false),
+                    "File {path:?} does not exist"
+                );
+
+                Ok(())
+            }
+        }
+    }
+
+    async fn check_http(url: &str) -> Result<()> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_eyre("Only plain http:// URLs are supported")?;
+        let (authority, path) = rest
+            .find('/')
+            .map_or((rest, "/"), |idx| (&rest[..idx], &rest[idx..]));
+        let address = if authority.contains(':') {
+            authority.to_owned()
+        } else {
+            format!("{authority}:80")
+        };
+
+        let mut stream = TcpStream::connect(&address)
+            .await
+            .wrap_err_with(|| format!("Failed to connect to {address}"))?;
+        let request =
+            format!("GET {path} HTTP/1.1\r\nHost: {authority}\r\nConnection: close\r\n\r\n");
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .wrap_err("Failed to send HTTP request")?;
+
+        let mut status_line = String::new();
+        BufReader::new(stream)
+            .read_line(&mut status_line)
+            .await
+            .wrap_err("Failed to read HTTP response")?;
+
+        let status = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse::<u16>().ok())
+            .ok_or_else(|| eyre!("Malformed HTTP status line: {status_line:?}"))?;
+        eyre::ensure!(
+            (200..300).contains(&status),
+            "HTTP probe returned status {status}"
+        );
+
+        Ok(())
+    }
+}
+
+/// Readiness probe configuration
+///
+/// Decides when a started service counts as ready
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Readiness {
+    /// The check to run
+    #[serde(flatten)]
+    pub probe: Probe,
+
+    /// How long (in milliseconds) a single check may take
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    pub timeout: Duration,
+
+    /// How long (in milliseconds) to wait between failed checks
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    pub interval: Duration,
+
+    /// Consecutive failed checks after which the service failed to start,
+    /// `None` to keep checking forever
+    #[serde(rename = "failureThreshold", default)]
+    pub failure_threshold: Option<usize>,
+}
+
+impl Readiness {
+    /// Run the probe until it succeeds
+    ///
+    /// Gives up once it failed `failure_threshold` times in a row, returning
+    /// the number of failed checks
+    pub async fn wait_until_ready(&self, target: &str) -> Result<(), usize> {
+        let mut failures = 0;
+
+        loop {
+            match self.probe.check(self.timeout).await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    failures += 1;
+                    debug!(target: target, "Readiness probe failed: {e}");
+
+                    if self
+                        .failure_threshold
+                        .is_some_and(|threshold| failures >= threshold)
+                    {
+                        warn!(target: target, "Readiness probe failed {failures} times in a row: {e}");
+                        return Err(failures);
+                    }
+                }
+            }
+
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+}
+
+/// Liveness health check configuration
+///
+/// Decides when a ready service has stopped responding
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Liveness {
+    /// The check to run
+    #[serde(flatten)]
+    pub probe: Probe,
+
+    /// How long (in milliseconds) a single check may take
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    pub timeout: Duration,
+
+    /// How long (in milliseconds) to wait between checks
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    pub interval: Duration,
+
+    /// Consecutive failed checks after which the service counts as unhealthy
+    #[serde(rename = "failureThreshold")]
+    pub failure_threshold: usize,
+}
+
+impl Liveness {
+    /// Run the probe on an interval until it fails `failure_threshold` times in a row
+    pub async fn wait_until_unhealthy(&self, target: &str) {
+        let mut failures = 0;
+
+        loop {
+            tokio::time::sleep(self.interval).await;
+
+            match self.probe.check(self.timeout).await {
+                Ok(()) => failures = 0,
+                Err(e) => {
+                    failures += 1;
+                    warn!(
+                        target: target,
+                        "Liveness probe failed ({failures}/{}): {e}", self.failure_threshold
+                    );
+
+                    if failures >= self.failure_threshold {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,16 +1,249 @@
+use std::{collections::HashMap, env, time::Duration};
+
 use eyre::{Error, Result, eyre};
 use libmprocs::CmdConfig;
 use serde::{Deserialize, Deserializer, Serialize};
+use serde_with::{DurationMilliSeconds, serde_as};
 
+#[serde_as]
 #[derive(Debug, Serialize, Deserialize)]
 /// Service process configuration
 pub struct Process {
     /// Argv used to run the service
     pub argv: ArgV,
+
+    /// How the service reports that it has started
+    #[serde(rename = "type", default)]
+    pub kind: ProcessType,
+
+    /// Watchdog timeout, the service has to send `WATCHDOG=1` at least this often
+    #[serde_as(as = "Option<DurationMilliSeconds<u64>>")]
+    #[serde(default)]
+    pub watchdog: Option<Duration>,
+
+    /// Environment variables to set for the service
+    #[serde(default)]
+    pub environment: HashMap<String, String>,
+
+    /// If the environment inherited from nimi should be cleared
+    #[serde(rename = "clearEnvironment", default)]
+    pub clear_environment: bool,
+
+    /// Written to `memory.max` of the cgroup of the service
+    #[serde(rename = "memoryMax", default)]
+    pub memory_max: Option<String>,
+
+    /// Written to `cpu.max` of the cgroup of the service
+    #[serde(rename = "cpuMax", default)]
+    pub cpu_max: Option<String>,
+
+    /// Written to `pids.max` of the cgroup of the service
+    #[serde(rename = "pidsMax", default)]
+    pub pids_max: Option<u64>,
+
+    /// Resource limits of the service
+    #[serde(default)]
+    pub limits: Limits,
+
+    /// Scheduling priority of the service, from -20 (highest) to 19 (lowest)
+    #[serde(default)]
+    pub nice: Option<i32>,
+
+    /// I/O scheduling class of the service
+    #[serde(rename = "ioSchedulingClass", default)]
+    pub io_scheduling_class: Option<IoSchedulingClass>,
+
+    /// I/O scheduling priority within the class, from 0 (highest) to 7 (lowest)
+    #[serde(rename = "ioSchedulingPriority", default)]
+    pub io_scheduling_priority: Option<u8>,
+
+    /// Adjustment of the score the OOM killer picks processes by, from -1000 to 1000
+    #[serde(rename = "oomScoreAdjust", default)]
+    pub oom_score_adjust: Option<i32>,
+
+    /// File mode creation mask of the service
+    #[serde(default)]
+    pub umask: Option<Umask>,
+}
+
+/// Resource Limits
+///
+/// Each limit is applied as both the soft and the hard limit, mirroring
+/// systemd's `LimitNOFILE=` and friends
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct Limits {
+    /// Maximum number of open file descriptors
+    #[serde(default)]
+    pub nofile: Option<Limit>,
+
+    /// Maximum size of core dumps in bytes
+    #[serde(default)]
+    pub core: Option<Limit>,
+
+    /// Maximum number of processes of the user running the service
+    #[serde(default)]
+    pub nproc: Option<Limit>,
+
+    /// Maximum size of the virtual memory of each process in bytes
+    #[serde(rename = "as", default)]
+    pub address_space: Option<Limit>,
+}
+
+/// Value of a resource limit, either a number or `"infinity"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "LimitRaw", into = "LimitRaw")]
+pub struct Limit(pub u64);
+
+/// Limit raw enum matching nix representation
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum LimitRaw {
+    Value(u64),
+    Keyword(String),
+}
+
+impl Limit {
+    /// A limit which doesn't limit anything
+    pub const INFINITY: Self = Self(u64::MAX);
+}
+
+impl TryFrom<LimitRaw> for Limit {
+    type Error = Error;
+
+    fn try_from(value: LimitRaw) -> Result<Self> {
+        match value {
+            LimitRaw::Value(value) => Ok(Self(value)),
+            LimitRaw::Keyword(keyword) if keyword == "infinity" => Ok(Self::INFINITY),
+            LimitRaw::Keyword(keyword) => Err(eyre!(
+                "Expected a number or \"infinity\" as limit. Got: {keyword:?}"
+            )),
+        }
+    }
+}
+
+impl From<Limit> for LimitRaw {
+    fn from(value: Limit) -> Self {
+        if value == Limit::INFINITY {
+            Self::Keyword("infinity".to_owned())
+        } else {
+            Self::Value(value.0)
+        }
+    }
+}
+
+/// I/O Scheduling Class
+///
+/// Mirrors systemd's `IOSchedulingClass=`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IoSchedulingClass {
+    /// Gets disk access before every other class
+    #[serde(rename = "realtime")]
+    Realtime,
+
+    /// The default class
+    #[serde(rename = "best-effort")]
+    BestEffort,
+
+    /// Only gets disk access when no other process needs it
+    #[serde(rename = "idle")]
+    Idle,
+}
+
+/// File mode creation mask, written as an octal string like `"0027"`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Umask(pub u32);
+
+impl TryFrom<String> for Umask {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        u32::from_str_radix(&value, 8)
+            .ok()
+            .filter(|mask| *mask <= 0o777)
+            .map(Self)
+            .ok_or_else(|| eyre!("Expected an octal umask like \"0027\". Got: {value:?}"))
+    }
+}
+
+impl From<Umask> for String {
+    fn from(value: Umask) -> Self {
+        format!("{:04o}", value.0)
+    }
+}
+
+/// Process Type
+///
+/// Mirrors systemd's `Type=` for the supported kinds of services
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProcessType {
+    /// The service is started once the process is spawned
+    #[default]
+    #[serde(rename = "simple")]
+    Simple,
+
+    /// The service is started once it sends `READY=1` over `NOTIFY_SOCKET`
+    #[serde(rename = "notify")]
+    Notify,
+
+    /// The service runs to completion, and is started once it exits successfully
+    #[serde(rename = "oneshot")]
+    Oneshot,
+}
+
+impl Process {
+    /// If the service needs a `NOTIFY_SOCKET` to talk to nimi
+    pub fn uses_notify_socket(&self) -> bool {
+        self.kind == ProcessType::Notify || self.watchdog.is_some()
+    }
+
+    /// If any process attribute applied before running the service is set
+    pub fn has_attributes(&self) -> bool {
+        let limits = self.limits;
+
+        [
+            limits.nofile,
+            limits.core,
+            limits.nproc,
+            limits.address_space,
+        ]
+        .iter()
+        .any(Option::is_some)
+            || self.nice.is_some()
+            || self.io_scheduling_class.is_some()
+            || self.io_scheduling_priority.is_some()
+            || self.oom_score_adjust.is_some()
+            || self.umask.is_some()
+    }
+
+    /// If any cgroup limit is set for the service
+    pub fn has_cgroup_limits(&self) -> bool {
+        self.memory_max.is_some() || self.cpu_max.is_some() || self.pids_max.is_some()
+    }
+
+    /// Get the environment changes in mprocs' representation
+    ///
+    /// Variables mapped to `None` are removed from the inherited environment,
+    /// which is how `clear_environment` gets applied.
+    pub fn mprocs_env<T>(&self) -> T
+    where
+        T: FromIterator<(String, Option<String>)>,
+    {
+        let cleared = env::vars()
+            .filter(|_| self.clear_environment)
+            .map(|(key, _)| (key, None));
+
+        let set = self
+            .environment
+            .iter()
+            .map(|(key, value)| (key.clone(), Some(value.clone())));
+
+        cleared.chain(set).collect()
+    }
 }
 
 /// List of args used to run a command
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ArgV(Vec<String>);
 
 impl ArgV {
@@ -4,29 +4,50 @@
 //! `Service`
 
 use std::{
+    collections::VecDeque,
+    ffi::OsString,
+    os::unix::process::ExitStatusExt,
     path::PathBuf,
+    pin::pin,
     process::{ExitStatus, Stdio},
     sync::Arc,
 };
 
 use eyre::{Context, Result};
-use log::{debug, info};
+use log::{debug, info, warn};
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
 use thiserror::Error;
-use tokio::time::timeout;
+use tokio::time::{Instant, timeout};
 use tokio::{
     process::{Child, Command},
-    task::JoinSet,
+    sync::{broadcast, watch},
+    task::{JoinHandle, JoinSet},
 };
 
+pub mod attributes;
+pub mod cgroup;
 pub mod config_dir;
 pub mod logger;
+pub mod notify;
+pub mod process_tree;
+pub mod state;
 
+pub use attributes::Attributes;
+pub use cgroup::Cgroup;
 pub use config_dir::ConfigDir;
 pub use logger::Logger;
+pub use notify::{NotifyMessage, NotifySocket, Watchdog};
+pub use process_tree::{Descendants, ProcessId};
+pub use state::{Dependency, ServiceState};
 use tokio_util::sync::CancellationToken;
 
-use crate::process_manager::{Service, Settings, settings::RestartMode};
-use crate::subreaper::{ChildGuard, Subreaper};
+use crate::process_manager::{
+    Service, Settings, Step, StepEnv,
+    service::{ExitKind, ProcessType},
+    settings::{Restart, RestartMode},
+};
+use crate::subreaper::{ChildGuard, Orphans, Subreaper};
 
 /// Responsible for the running of and managing of service state
 pub struct ServiceManager {
@@ -36,10 +57,53 @@ pub struct ServiceManager {
     name: Arc<String>,
     service: Service,
 
+    restart: Restart,
     current_restart_count: usize,
+    recent_restarts: VecDeque<Instant>,
+    last_exit: Option<ExitStatus>,
+
+    state: watch::Sender<ServiceState>,
+    dependencies: Vec<Dependency>,
+    dependents: Vec<watch::Receiver<ServiceState>>,
+    signals: broadcast::Receiver<Signal>,
+    orphans: Arc<Orphans>,
+    cgroup: Option<Cgroup>,
 
     config_dir: ConfigDir,
     logs_dir: Arc<Option<PathBuf>>,
+    notify: Option<NotifySocket>,
+    main_pid: Option<ProcessId>,
+}
+
+/// Everything needed to run the hooks of a service, also outside of its manager
+struct HookRunner {
+    name: Arc<String>,
+    logs_dir: Arc<Option<PathBuf>>,
+    stop_timeout: std::time::Duration,
+    env: StepEnv,
+}
+
+impl HookRunner {
+    /// Run the hooks for one point in the lifecycle of the service, in order
+    ///
+    /// Hooks log under `<service>-<hook>`
+    async fn run(&self, hook: &str, steps: &[Step], cancel_tok: &CancellationToken) -> Result<()> {
+        let target = Arc::new(format!("{}-{hook}", self.name));
+
+        for step in steps {
+            step.run(
+                &target,
+                &self.logs_dir,
+                cancel_tok,
+                self.stop_timeout,
+                &self.env,
+            )
+            .await
+            .wrap_err_with(|| format!("The {hook} hook of {} failed", self.name))?;
+        }
+
+        Ok(())
+    }
 }
 
 /// Errors which can occur during service management
@@ -51,6 +115,95 @@ pub enum ServiceError {
         /// Exit status
         status: ExitStatus,
     },
+
+    /// Error for when the liveness probe of the service keeps failing
+    #[error("Service failed its liveness probe {failures} times in a row")]
+    Unhealthy {
+        /// Number of consecutive failed checks
+        failures: usize,
+    },
+
+    /// Error for when the readiness probe of the service keeps failing
+    #[error("Service failed its readiness probe {failures} times in a row")]
+    NotReady {
+        /// Number of consecutive failed checks
+        failures: usize,
+    },
+
+    /// Error for when the service misses its watchdog deadline
+    #[error("Service missed its watchdog deadline")]
+    WatchdogTimeout,
+
+    /// Error for when a service marked as critical fails
+    #[error("Critical service {name:?} failed")]
+    CriticalServiceFailed {
+        /// Name of the failed service
+        name: Arc<String>,
+        /// Exit status of the last run of the service, if it exited on its own
+        status: Option<ExitStatus>,
+    },
+
+    /// Error for when a `preStart` or `postStart` hook fails
+    #[error("The {hook} hook of the service failed")]
+    HookFailed {
+        /// Name of the hook
+        hook: &'static str,
+    },
+
+    /// Error for when a step doesn't finish within its timeout
+    #[error("Step timed out after {timeout:?}")]
+    StepTimedOut {
+        /// The timeout of the step
+        timeout: std::time::Duration,
+    },
+
+    /// Error for when a dependency listed in `requires` fails
+    #[error("Required dependency {dependency:?} failed")]
+    DependencyFailed {
+        /// Name of the failed dependency
+        dependency: Arc<String>,
+    },
+}
+
+impl ServiceError {
+    /// Get the exit status of the process behind this error, if there is one
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        match self {
+            Self::ProcessExited { status } => Some(*status),
+            // Services also fail after clean exits, e.g. by hitting the restart limit
+            Self::CriticalServiceFailed { status, .. } => status.filter(|status| !status.success()),
+            Self::Unhealthy { .. }
+            | Self::NotReady { .. }
+            | Self::HookFailed { .. }
+            | Self::WatchdogTimeout
+            | Self::StepTimedOut { .. }
+            | Self::DependencyFailed { .. } => None,
+        }
+    }
+}
+
+/// How a service ended once it won't be started again
+#[derive(Debug, Clone, Copy)]
+pub struct ServiceOutcome {
+    /// Terminal state of the service
+    pub state: ServiceState,
+
+    /// Exit status of the last run of the service, if it exited on its own
+    pub status: Option<ExitStatus>,
+}
+
+impl ServiceOutcome {
+    /// Get the exit status to report for the service, e.g. with `codeFrom`
+    ///
+    /// Failed services are reported with exit code `1` when they have no failed
+    /// exit status of their own, e.g. because a dependency or probe failed
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        match self.status {
+            Some(status) if !status.success() => Some(status),
+            _ if self.state == ServiceState::Failed => Some(ExitStatus::from_raw(1 << 8)),
+            status => status,
+        }
+    }
 }
 
 /// Used to initialize the Service Manager in a structured manner
@@ -59,6 +212,8 @@ pub struct ServiceManagerOpts {
     pub logs_dir: Arc<Option<PathBuf>>,
     /// Temporary directory
     pub tmp_dir: Arc<PathBuf>,
+    /// Cgroup to create the cgroup of the service in, if cgroups are enabled
+    pub cgroup_root: Option<Arc<PathBuf>>,
 
     /// Process manager settings
     pub settings: Arc<Settings>,
@@ -71,6 +226,18 @@ pub struct ServiceManagerOpts {
 
     /// Cancellation token
     pub cancel_tok: CancellationToken,
+
+    /// Publishes the state of this service
+    pub state: watch::Sender<ServiceState>,
+
+    /// Services to start before this one
+    pub dependencies: Vec<Dependency>,
+
+    /// States of the services to stop before this one
+    pub dependents: Vec<watch::Receiver<ServiceState>>,
+
+    /// Signals received by nimi which may have to be forwarded
+    pub signals: broadcast::Receiver<Signal>,
 }
 
 impl ServiceManager {
@@ -79,10 +246,31 @@ impl ServiceManager {
     /// This creates the corresponding processes and supervises the operation for a given
     /// `Service`.
     ///
-    /// This also produces a `ConfigDir` instance per service.
+    /// This also produces a `ConfigDir` instance per service, and a `NotifySocket` for
+    /// services which use one.
     pub async fn new(opts: ServiceManagerOpts) -> Result<Self> {
+        let notify = opts
+            .service
+            .process
+            .uses_notify_socket()
+            .then(|| NotifySocket::bind(&opts.tmp_dir, &opts.name))
+            .transpose()?;
+
+        let restart = opts.settings.restart.with_overrides(&opts.service.restart);
+        let orphans = Orphans::new(Arc::clone(&opts.name));
+        let cgroup = opts
+            .cgroup_root
+            .as_deref()
+            .map(|root| Cgroup::new(root, &opts.name, &opts.service.process))
+            .transpose()?;
+        if let Some(cgroup) = &cgroup {
+            Subreaper::track_cgroup(cgroup.path(), &orphans);
+        }
+
         Ok(Self {
             config_dir: ConfigDir::new(&opts.tmp_dir, &opts.service.config_data).await?,
+            notify,
+            main_pid: None,
 
             settings: opts.settings,
             cancel_tok: opts.cancel_tok,
@@ -90,51 +278,220 @@ impl ServiceManager {
             name: opts.name,
             service: opts.service,
 
+            restart,
             current_restart_count: 0,
+            recent_restarts: VecDeque::new(),
+            last_exit: None,
+
+            state: opts.state,
+            dependencies: opts.dependencies,
+            dependents: opts.dependents,
+            signals: opts.signals,
+            orphans,
+            cgroup,
+
             logs_dir: opts.logs_dir,
         })
     }
 
     /// Run the `Service` managed by this `ServiceManager`
     ///
-    /// This will handle restarts, attach logging processes and manage linking the config
-    /// directory.
-    pub async fn run(&mut self) -> Result<()> {
-        while let Err(e) = self.spawn_service_process().await {
-            match e.downcast_ref() {
-                Some(ServiceError::ProcessExited { status }) => {
-                    info!("Process {} exited with status {}", &self.name, status)
+    /// This will wait for dependencies, handle restarts, attach logging processes and
+    /// manage linking the config directory.
+    ///
+    /// Fails if the service is critical and ends up failed. Failures of non critical
+    /// services are only logged, unless the service couldn't be set up or
+    /// spawned at all.
+    ///
+    /// Returns the terminal state of the service and the exit status of its last run
+    pub async fn run(&mut self) -> Result<ServiceOutcome> {
+        let result = self.supervise().await;
+
+        let orphans = self.orphans.reaped();
+        if orphans > 0 {
+            info!(target: &self.name, "Reaped {orphans} orphaned processes of the service");
+        }
+
+        if let Some(cgroup) = &self.cgroup {
+            // Its files are gone once it was removed
+            Self::log_usage(&self.name, cgroup);
+            if let Err(e) = cgroup.remove(self.stop_timeout()).await {
+                warn!(target: &self.name, "Failed to remove the cgroup of the service: {e:#}");
+            }
+            Subreaper::untrack_cgroup(cgroup.path());
+        }
+
+        self.state.send_replace(match &result {
+            Ok(state) => *state,
+            Err(_) => ServiceState::Failed,
+        });
+
+        match result {
+            Ok(ServiceState::Failed) if self.service.critical => {
+                Err(ServiceError::CriticalServiceFailed {
+                    name: Arc::clone(&self.name),
+                    status: self.last_exit,
+                }
+                .into())
+            }
+            Ok(ServiceState::Failed) => {
+                warn!(
+                    "Service {} failed, other services keep running as it is not critical",
+                    self.name
+                );
+                Ok(self.outcome(ServiceState::Failed))
+            }
+            // Errors setting up or spawning the service are never downgraded
+            result => result.map(|state| self.outcome(state)),
+        }
+    }
+
+    fn outcome(&self, state: ServiceState) -> ServiceOutcome {
+        ServiceOutcome {
+            state,
+            status: self.last_exit,
+        }
+    }
+
+    /// Supervise the service until it won't be started again
+    ///
+    /// Returns the terminal state the service ended up in
+    async fn supervise(&mut self) -> Result<ServiceState> {
+        let cancel_tok = self.cancel_tok.clone();
+        let failed_dependency = tokio::select! {
+            failed = Dependency::wait_for_all(&mut self.dependencies) => failed,
+            _ = cancel_tok.cancelled() => return Ok(ServiceState::Stopped),
+        };
+
+        if let Some(dependency) = failed_dependency {
+            info!(
+                "Not starting {}, required dependency {} failed",
+                self.name, dependency
+            );
+            return Ok(ServiceState::Failed);
+        }
+
+        loop {
+            let started = Instant::now();
+            self.last_exit = None;
+            let exit = match self.spawn_service_process().await {
+                Ok(None) => break,
+                Ok(Some(status)) => {
+                    self.last_exit = Some(status);
+                    info!("Process {} exited with status {}", &self.name, status);
+                    let exit = ExitKind::classify(status, &self.service.success_exit_status);
+
+                    if self.service.process.kind == ProcessType::Oneshot
+                        && exit == ExitKind::Success
+                    {
+                        info!(target: &self.name, "Service completed");
+                        return Ok(ServiceState::Completed);
+                    }
+
+                    if self.service.restart_prevent_exit_status.contains(status) {
+                        info!(
+                            "Not restarting {}, exit status prevents restarts",
+                            self.name
+                        );
+                        return Ok(Self::final_state(exit));
+                    }
+
+                    exit
                 }
-                None => return Err(e),
+                Err(e) => match e.downcast_ref() {
+                    Some(ServiceError::Unhealthy { failures }) => {
+                        info!(
+                            "Process {} was stopped after {} failed liveness probes",
+                            &self.name, failures
+                        );
+                        ExitKind::Abnormal
+                    }
+                    Some(ServiceError::NotReady { failures }) => {
+                        info!(
+                            "Process {} was stopped after {} failed readiness probes",
+                            &self.name, failures
+                        );
+                        ExitKind::Failure
+                    }
+                    Some(ServiceError::HookFailed { hook }) => {
+                        info!("The {} hook of {} failed", hook, &self.name);
+                        ExitKind::Failure
+                    }
+                    Some(ServiceError::WatchdogTimeout) => {
+                        info!(
+                            "Process {} was stopped after missing its watchdog deadline",
+                            &self.name
+                        );
+                        ExitKind::Abnormal
+                    }
+                    Some(ServiceError::DependencyFailed { dependency }) => {
+                        info!(
+                            "Stopped {}, required dependency {} failed",
+                            self.name, dependency
+                        );
+                        return Ok(ServiceState::Failed);
+                    }
+                    Some(
+                        ServiceError::ProcessExited { .. }
+                        | ServiceError::CriticalServiceFailed { .. }
+                        | ServiceError::StepTimedOut { .. },
+                    )
+                    | None => return Err(e),
+                },
+            };
+
+            if !self.restart.condition.restarts_on(exit) {
+                info!(
+                    "Not restarting {} (condition: {:?})",
+                    self.name, self.restart.condition
+                );
+                return Ok(Self::final_state(exit));
+            }
+
+            if self
+                .restart
+                .reset_after
+                .is_some_and(|reset_after| started.elapsed() >= reset_after)
+            {
+                debug!(target: &self.name, "Resetting restart count after stable run");
+                self.current_restart_count = 0;
+            }
+
+            // Restarting too often is a failure, even if every run exited cleanly
+            if !matches!(self.restart.mode, RestartMode::Never) && self.restart_limit_hit() {
+                return Ok(ServiceState::Failed);
             }
 
-            match self.settings.restart.mode {
+            match self.restart.mode {
                 RestartMode::Always => info!("restarting (mode: always)"),
                 RestartMode::UpToCount => {
-                    if self.current_restart_count >= self.settings.restart.count {
+                    if self.current_restart_count >= self.restart.count {
                         info!(
                             "Not restarting (mode: up-to-count {}/{})",
-                            self.current_restart_count, self.settings.restart.count
+                            self.current_restart_count, self.restart.count
                         );
-                        break;
+                        return Ok(Self::final_state(exit));
                     }
 
-                    self.current_restart_count += 1;
-
                     info!(
                         "Restarting (mode: up-to-count {}/{})",
-                        self.current_restart_count, self.settings.restart.count
+                        self.current_restart_count + 1,
+                        self.restart.count
                     );
                 }
                 RestartMode::Never => {
                     info!("Not restarting (mode: never)");
 
-                    break;
+                    return Ok(Self::final_state(exit));
                 }
             }
 
+            let delay = self.restart.delay(self.current_restart_count);
+            self.current_restart_count += 1;
+            debug!(target: &self.name, "Restarting in {delay:?}");
+
             tokio::select! {
-                _ = tokio::time::sleep(self.settings.restart.time) => {},
+                _ = tokio::time::sleep(delay) => {},
                 _ = self.cancel_tok.cancelled() => {
                     info!("Received shutdown during restart delay for {}", self.name);
                     break;
@@ -142,15 +499,104 @@ impl ServiceManager {
             }
         }
 
-        Ok(())
+        Ok(ServiceState::Stopped)
+    }
+
+    /// Get the terminal state of a service which won't be restarted after `exit`
+    fn final_state(exit: ExitKind) -> ServiceState {
+        match exit {
+            ExitKind::Success => ServiceState::Stopped,
+            ExitKind::Failure | ExitKind::Abnormal => ServiceState::Failed,
+        }
+    }
+
+    /// Record a restart, checking it against `Restart::limit`
+    ///
+    /// Returns `true` when the service has been restarted too often
+    /// recently and should not be restarted again
+    fn restart_limit_hit(&mut self) -> bool {
+        let Some(limit) = self.restart.limit else {
+            return false;
+        };
+
+        let now = Instant::now();
+        while self
+            .recent_restarts
+            .front()
+            .is_some_and(|restart| now.duration_since(*restart) >= limit.interval)
+        {
+            self.recent_restarts.pop_front();
+        }
+
+        if self.recent_restarts.len() >= limit.burst {
+            info!(
+                "Not restarting {}, hit the limit of {} restarts within {:?}",
+                self.name, limit.burst, limit.interval
+            );
+            return true;
+        }
+
+        self.recent_restarts.push_back(now);
+        false
     }
 
     /// Spawns a service process
     ///
     /// Attaches loggers and `wait`s on the process, forwarding
     /// shutdown sequeneces
-    pub async fn spawn_service_process(&mut self) -> Result<()> {
-        let (mut process, _child_guard) = self.create_service_child().await?;
+    ///
+    /// Returns the exit status if the process exited on its own, or `None`
+    /// if it was stopped during shutdown
+    pub async fn spawn_service_process(&mut self) -> Result<Option<ExitStatus>> {
+        if let Err(e) = self
+            .hook_runner(Vec::new())
+            .run("preStart", &self.service.hooks.pre_start, &self.cancel_tok)
+            .await
+        {
+            warn!(target: &self.name, "{e:#}");
+            eyre::bail!(ServiceError::HookFailed { hook: "preStart" });
+        }
+
+        if self.cancel_tok.is_cancelled() {
+            return Ok(None);
+        }
+
+        // Messages an earlier process sent right before exiting, e.g. `READY=1`,
+        // mustn't count for the next one
+        let dropped = self.notify.as_ref().map_or(0, NotifySocket::drain);
+        if dropped > 0 {
+            debug!(target: &self.name, "Dropped {dropped} notify messages of an earlier process");
+        }
+
+        let (mut process, child_guard) = self.create_service_child().await?;
+        let result = self.watch_service_process(&mut process).await;
+
+        let status = process.try_wait().ok().flatten();
+        drop(child_guard);
+
+        let extra_env = status.map(Self::exit_env).unwrap_or_default();
+        if let Err(e) = self
+            .hook_runner(extra_env)
+            .run(
+                "postStop",
+                &self.service.hooks.post_stop,
+                &CancellationToken::new(),
+            )
+            .await
+        {
+            warn!(target: &self.name, "{e:#}");
+        }
+
+        result
+    }
+
+    /// Attach loggers to a spawned service process and watch it until it exits
+    /// or has to be stopped
+    async fn watch_service_process(&mut self, process: &mut Child) -> Result<Option<ExitStatus>> {
+        self.drop_pending_signals();
+
+        // Gone once the process was waited on
+        let pgid = process.id();
         let mut set = JoinSet::new();
 
         Logger::Stdout.start(
@@ -166,41 +612,453 @@ impl ServiceManager {
             &mut set,
         )?;
 
+        let readiness = self.service.readiness.as_ref();
+        let kind = self.service.process.kind;
+        let mut ready = readiness.is_none() && kind == ProcessType::Simple;
+        let name = Arc::clone(&self.name);
+        let mut wait_until_ready = pin!(async move {
+            match readiness {
+                Some(readiness) => readiness.wait_until_ready(&name).await,
+                None => Ok(()),
+            }
+        });
+
+        // Only polled once ready, so the first check happens an interval after that
+        let liveness = self.service.liveness.as_ref();
+        let name = Arc::clone(&self.name);
+        let mut wait_until_unhealthy = pin!(async move {
+            match liveness {
+                Some(liveness) => {
+                    liveness.wait_until_unhealthy(&name).await;
+                    liveness.failure_threshold
+                }
+                None => std::future::pending().await,
+            }
+        });
+
+        let mut watchdog = Watchdog::new(self.service.process.watchdog);
+
+        // Stops the postStart hooks once the process stops being watched
+        let hooks_tok = self.cancel_tok.child_token();
+        let _hooks_guard = hooks_tok.clone().drop_guard();
+        let mut post_start = None;
+
+        // Also replaces `Ready` of an earlier run while the postStart hooks run
+        self.state.send_replace(ServiceState::Started);
+        if ready {
+            watchdog.reset();
+            post_start = self.start_post_start(&hooks_tok);
+        }
+
+        let mut exit = None;
+        self.main_pid = None;
+
+        loop {
+            tokio::select! {
+                _ = self.cancel_tok.cancelled() => {
+                    debug!(target: &self.name, "Received shutdown signal");
+                    ServiceState::wait_for_terminal(&mut self.dependents).await;
+                    self.stop_process(process).await?;
+                    break;
+                }
+                dependency = Dependency::wait_for_required_failure(&mut self.dependencies) => {
+                    self.stop_process(process).await?;
+                    eyre::bail!(ServiceError::DependencyFailed { dependency });
+                }
+                status = process.wait() => {
+                    exit = Some(status.wrap_err("Failed to get process status")?);
+                    break;
+                }
+                failures = &mut wait_until_unhealthy, if ready => {
+                    self.stop_process(process).await?;
+                    eyre::bail!(ServiceError::Unhealthy { failures });
+                }
+                result = &mut wait_until_ready, if !ready && kind == ProcessType::Simple => {
+                    if let Err(failures) = result {
+                        self.stop_process(process).await?;
+                        eyre::bail!(ServiceError::NotReady { failures });
+                    }
+
+                    ready = true;
+                    watchdog.reset();
+                    post_start = self.start_post_start(&hooks_tok);
+                }
+                result = Self::join_post_start(&mut post_start) => {
+                    post_start = None;
+
+                    if let Err(e) = result {
+                        warn!(target: &self.name, "{e:#}");
+                        self.stop_process(process).await?;
+                        eyre::bail!(ServiceError::HookFailed { hook: "postStart" });
+                    }
+
+                    self.set_ready();
+                }
+                messages = Self::recv_notify(self.notify.as_ref()) => {
+                    let messages = messages.unwrap_or_else(|e| {
+                        warn!(target: &self.name, "{e:?}");
+                        Vec::new()
+                    });
+
+                    for message in messages {
+                        match message {
+                            NotifyMessage::Ready if kind == ProcessType::Notify && !ready => {
+                                ready = true;
+                                watchdog.reset();
+                                post_start = self.start_post_start(&hooks_tok);
+                            }
+                            NotifyMessage::Ready => {}
+                            NotifyMessage::Stopping => {
+                                debug!(target: &self.name, "Service is stopping");
+                            }
+                            NotifyMessage::Status(status) => {
+                                info!(target: &self.name, "Status: {status}");
+                            }
+                            NotifyMessage::MainPid(pid) => {
+                                if let Some(main_pid) = self.main_pid_of(process, pid) {
+                                    self.main_pid = Some(main_pid);
+                                }
+                            }
+                            NotifyMessage::Watchdog => watchdog.reset(),
+                            NotifyMessage::WatchdogTrigger => watchdog.trigger(),
+                        }
+                    }
+                }
+                signal = Self::recv_signal(&mut self.signals) => {
+                    self.forward_signal(process, signal);
+                }
+                () = watchdog.expired() => {
+                    warn!(target: &self.name, "Service missed its watchdog deadline");
+                    self.stop_process(process).await?;
+                    eyre::bail!(ServiceError::WatchdogTimeout);
+                }
+            }
+        }
+
+        Self::join_loggers(set, pgid, self.stop_timeout(), &self.name).await?;
+
+        Ok(exit)
+    }
+
+    /// Wait for the loggers of an exited process to reach the end of its output
+    ///
+    /// The output is logged for as long as any process of its group `pgid`
+    /// runs. Processes which escaped the group, e.g. daemons, may keep the output
+    /// open forever, so they are only waited on up to `timeout_duration` after
+    /// the group is gone, before their output is no longer logged
+    pub async fn join_loggers(
+        set: JoinSet<Result<()>>,
+        pgid: Option<u32>,
+        timeout_duration: std::time::Duration,
+        target: &str,
+    ) -> Result<()> {
+        let mut loggers = pin!(set.join_all());
+
         tokio::select! {
-            _ = self.cancel_tok.cancelled() => {
-                debug!(target: &self.name, "Received shutdown signal");
-                Self::shutdown_process(&mut process, self.settings.restart.time).await?;
-            }
-            status = process.wait() => {
-                let status = status.wrap_err("Failed to get process status")?;
-                eyre::ensure!(
-                    status.success(),
-                    ServiceError::ProcessExited { status }
-                );
+            results = &mut loggers => return results.into_iter().collect(),
+            () = Self::wait_for_group_exit(pgid) => {}
+        }
+
+        match timeout(timeout_duration, loggers).await {
+            Ok(results) => results.into_iter().collect(),
+            Err(_) => {
+                warn!(target: target, "Leftover processes keep the output open, no longer logging it");
+                Ok(())
             }
         }
+    }
+
+    /// Wait until no process of the process group `pgid` is left
+    async fn wait_for_group_exit(pgid: Option<u32>) {
+        let Some(pgid) = pgid else {
+            return;
+        };
 
-        set.join_all().await.into_iter().collect()
+        let pgid = Pid::from_raw(pgid as i32);
+        while nix::sys::signal::killpg(pgid, None).is_ok() {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Start the `postStart` hooks of a service which just became ready
+    ///
+    /// They run in their own task, so the process keeps being watched in the
+    /// meantime. Without hooks the service is reported as ready right away.
+    fn start_post_start(&self, cancel_tok: &CancellationToken) -> Option<JoinHandle<Result<()>>> {
+        if self.service.hooks.post_start.is_empty() {
+            self.set_ready();
+            return None;
+        }
+
+        let runner = self.hook_runner(Vec::new());
+        let steps = self.service.hooks.post_start.clone();
+        let cancel_tok = cancel_tok.clone();
+
+        Some(tokio::spawn(async move {
+            runner.run("postStart", &steps, &cancel_tok).await
+        }))
     }
 
-    /// Kill a service process gracefully
+    /// Wait for the `postStart` hooks to finish
+    ///
+    /// Never resolves while they aren't running
+    async fn join_post_start(task: &mut Option<JoinHandle<Result<()>>>) -> Result<()> {
+        match task {
+            Some(task) => task.await.wrap_err("The postStart hooks panicked")?,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Report the service as ready to its dependents
+    fn set_ready(&self) {
+        info!(target: &self.name, "Service is ready");
+        self.state.send_replace(ServiceState::Ready);
+    }
+
+    /// Prepare running hooks in the same environment as the service process,
+    /// with `extra_env` on top
+    fn hook_runner(&self, extra_env: Vec<(&'static str, String)>) -> HookRunner {
+        let process = &self.service.process;
+
+        let vars = std::iter::once((
+            OsString::from("XDG_CONFIG_HOME"),
+            self.config_dir.as_ref().to_owned(),
+        ))
+        .chain(
+            process
+                .environment
+                .iter()
+                .map(|(key, value)| (key.into(), value.into())),
+        )
+        .chain(
+            extra_env
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into())),
+        )
+        .collect();
+
+        HookRunner {
+            name: Arc::clone(&self.name),
+            logs_dir: Arc::clone(&self.logs_dir),
+            stop_timeout: self.stop_timeout(),
+            env: StepEnv {
+                clear: process.clear_environment,
+                vars,
+            },
+        }
+    }
+
+    /// Describe an exit status the way systemd does for `ExecStopPost=`
+    ///
+    /// `EXIT_CODE` is `exited`, `killed` or `dumped`, and `EXIT_STATUS` is
+    /// either the exit code or the name of the signal without its `SIG` prefix
+    fn exit_env(status: ExitStatus) -> Vec<(&'static str, String)> {
+        let (code, status) = match (status.code(), status.signal()) {
+            (Some(code), _) => ("exited", code.to_string()),
+            (None, Some(signal)) => (
+                if status.core_dumped() {
+                    "dumped"
+                } else {
+                    "killed"
+                },
+                Signal::try_from(signal).map_or_else(
+                    |_| signal.to_string(),
+                    |signal| signal.as_str().trim_start_matches("SIG").to_owned(),
+                ),
+            ),
+            (None, None) => ("exited", String::new()),
+        };
+
+        vec![("EXIT_CODE", code.to_owned()), ("EXIT_STATUS", status)]
+    }
+
+    /// Run the `preStop` hooks, then stop the service process gracefully
+    async fn stop_process(&self, process: &mut Child) -> Result<()> {
+        if let Err(e) = self
+            .hook_runner(Vec::new())
+            .run(
+                "preStop",
+                &self.service.hooks.pre_stop,
+                &CancellationToken::new(),
+            )
+            .await
+        {
+            warn!(target: &self.name, "{e:#}");
+        }
+
+        Self::shutdown_process(
+            process,
+            &self.service.stop_signals,
+            self.stop_timeout(),
+            self.cgroup.as_ref(),
+            self.main_pid,
+        )
+        .await
+    }
+
+    /// Check the main pid reported through `MAINPID=`, which gets signalled and
+    /// stopped along with the service process
+    ///
+    /// Only processes of the service are accepted: descendants of the service
+    /// process, members of its process group or cgroup, and orphans adopted by
+    /// nimi, as daemons which forked twice end up as
+    fn main_pid_of(&self, process: &Child, pid: u32) -> Option<ProcessId> {
+        let service_pid = Pid::from_raw(process.id()? as i32);
+        let pid = Pid::from_raw(i32::try_from(pid).ok()?);
+
+        let belongs = pid == service_pid
+            || Descendants::of(service_pid).contains(pid)
+            || nix::unistd::getpgid(Some(pid)) == Ok(service_pid)
+            || self
+                .cgroup
+                .as_ref()
+                .is_some_and(|cgroup| cgroup.contains(pid));
+
+        let main_pid = ProcessId::new(pid);
+        let adopted = main_pid.and_then(|main_pid| main_pid.parent()) == Some(Pid::this())
+            && Subreaper::is_orphan(pid.as_raw() as u32);
+
+        match main_pid {
+            Some(main_pid) if belongs || adopted => {
+                debug!(target: &self.name, "Main pid is {pid}");
+                Some(main_pid)
+            }
+            _ => {
+                warn!(target: &self.name, "Ignoring main pid {pid}, which isn't a process of the service");
+                None
+            }
+        }
+    }
+
+    /// Log the resources the service used
+    fn log_usage(name: &str, cgroup: &Cgroup) {
+        let usage = cgroup.usage();
+        if let Some(cpu) = usage.cpu {
+            info!(target: name, "Service used {cpu:.2?} of CPU time");
+        }
+        if let Some(peak) = usage.memory_peak {
+            info!(target: name, "Service used at most {} MiB of memory", peak / (1024 * 1024));
+        }
+    }
+
+    /// How long the service gets to exit after being asked to stop
+    fn stop_timeout(&self) -> std::time::Duration {
+        self.service
+            .stop_timeout
+            .unwrap_or(self.settings.stop_timeout)
+    }
+
+    /// Receive the next messages from the notify socket of the service
+    ///
+    /// Never resolves if the service has no notify socket
+    async fn recv_notify(notify: Option<&NotifySocket>) -> Result<Vec<NotifyMessage>> {
+        match notify {
+            Some(notify) => notify.recv().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Receive the next signal received by nimi
+    ///
+    /// Never resolves once no more signals can be received
+    async fn recv_signal(signals: &mut broadcast::Receiver<Signal>) -> Signal {
+        loop {
+            match signals.recv().await {
+                Ok(signal) => return signal,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => std::future::pending().await,
+            }
+        }
+    }
+
+    /// Drop signals received while no process of the service was running
+    ///
+    /// They were meant for an earlier process, so forwarding them to this one
+    /// could e.g. reload it right after it started.
+    fn drop_pending_signals(&mut self) {
+        loop {
+            match self.signals.try_recv() {
+                Ok(signal) => {
+                    if self.service.forward_signals.contains(&signal) {
+                        warn!(target: &self.name, "Dropping {signal}, which was received while the service wasn't running");
+                    }
+                }
+                Err(broadcast::error::TryRecvError::Lagged(count)) => {
+                    warn!(target: &self.name, "Dropping {count} signals, which were received while the service wasn't running");
+                }
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// Forward a signal to the service process, if the service asked for it
+    ///
+    /// Goes to the main pid instead while the one reported by the service runs.
+    /// Unlike stop signals it isn't sent to the whole process group, as e.g.
+    /// nginx workers or a shell's children would rather exit on `SIGHUP` than
+    /// reload, which is also why `tini` and systemd's `$MAINPID` do the same.
+    fn forward_signal(&self, process: &Child, signal: Signal) {
+        if !self.service.forward_signals.contains(&signal) {
+            return;
+        }
+
+        let pid = self
+            .main_pid
+            .and_then(|main_pid| main_pid.pid())
+            .or_else(|| process.id().map(|pid| Pid::from_raw(pid as i32)));
+
+        if let Some(pid) = pid {
+            debug!(target: &self.name, "Forwarding {signal} to {pid}");
+            let _ = nix::sys::signal::kill(pid, signal);
+        }
+    }
+
+    /// Kill a service process and its process group gracefully
+    ///
+    /// Sends each of `signals` in order to the process group, waiting up to
+    /// `timeout_duration` for the whole group to exit after each one, before
+    /// killing it with `SIGKILL`. Descendants which moved to another process
+    /// group or session are treated as part of the group, as is every process
+    /// in `cgroup` and the main pid with its descendants.
     pub async fn shutdown_process(
         process: &mut Child,
+        signals: &[Signal],
         timeout_duration: std::time::Duration,
+        cgroup: Option<&Cgroup>,
+        main_pid: Option<ProcessId>,
     ) -> Result<()> {
         #[cfg(unix)]
         {
-            use nix::sys::signal::{Signal, kill};
-            use nix::unistd::Pid;
+            use nix::sys::signal::killpg;
 
             if let Some(pid) = process.id() {
-                let pid = Pid::from_raw(pid as i32);
-                let _ = kill(pid, Signal::SIGTERM);
-                if timeout(timeout_duration, process.wait()).await.is_err() {
-                    let _ = kill(pid, Signal::SIGKILL);
-                    let _ = process.wait().await;
+                // Processes are spawned as leaders of their own process group
+                let pgid = Pid::from_raw(pid as i32);
+                let mut descendants = Descendants::of(pgid);
+                if let Some(main_pid) = main_pid {
+                    descendants.add(main_pid);
+                }
+
+                for &signal in signals {
+                    let _ = killpg(pgid, signal);
+                    descendants.kill(signal);
+                    if let Some(cgroup) = cgroup {
+                        cgroup.kill(signal);
+                    }
+
+                    let exited = Self::wait_for_group(process, pgid, &descendants, cgroup);
+                    if timeout(timeout_duration, exited).await.is_ok() {
+                        return Ok(());
+                    }
                 }
 
+                let _ = killpg(pgid, Signal::SIGKILL);
+                descendants.kill(Signal::SIGKILL);
+                if let Some(cgroup) = cgroup {
+                    cgroup.kill(Signal::SIGKILL);
+                }
+                let _ = process.wait().await;
+
                 return Ok(());
             }
         }
@@ -211,15 +1069,66 @@ impl ServiceManager {
             .wrap_err("Failed to kill service process")
     }
 
+    /// Wait until the process, every other process in its group or cgroup and
+    /// its known descendants exited
+    #[cfg(unix)]
+    async fn wait_for_group(
+        process: &mut Child,
+        pgid: Pid,
+        descendants: &Descendants,
+        cgroup: Option<&Cgroup>,
+    ) {
+        let _ = process.wait().await;
+
+        // Leftover processes are orphans now, which get reaped by the subreaper
+        while nix::sys::signal::killpg(pgid, None).is_ok()
+            || descendants.any_alive()
+            || cgroup.is_some_and(Cgroup::is_populated)
+        {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    }
+
     /// Create service child
     ///
     /// Responsible for creating the actual child process for the
     /// service
     pub async fn create_service_child(&self) -> Result<(Child, ChildGuard)> {
+        let mut command = Command::new(self.service.process.argv.binary());
+
+        if self.service.process.clear_environment {
+            command.env_clear();
+        }
+
+        // The notify socket of nimi's own supervisor is only meant for nimi
+        match &self.notify {
+            Some(notify) => command.env("NOTIFY_SOCKET", notify.path()),
+            None => command.env_remove("NOTIFY_SOCKET"),
+        };
+
+        if let Some(watchdog) = self.service.process.watchdog {
+            command.env("WATCHDOG_USEC", watchdog.as_micros().to_string());
+        }
+
+        if self.service.process.has_attributes() {
+            Attributes::new(&self.service.process).apply(&mut command);
+        }
+
+        let _procs = self
+            .cgroup
+            .as_ref()
+            .map(|cgroup| cgroup.place(&mut command))
+            .transpose()?;
+
         let _pause = Subreaper::pause_reaping();
-        let process = Command::new(self.service.process.argv.binary())
+        let process = command
             .args(self.service.process.argv.args())
             .env("XDG_CONFIG_HOME", &self.config_dir)
+            .envs(&self.service.process.environment)
+            // Run in a separate process group, so stopping reaches forked workers too.
+            // Background groups can't read from the terminal, so don't inherit stdin.
+            .process_group(0)
+            .stdin(Stdio::null())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .kill_on_drop(true)
@@ -233,6 +1142,7 @@ impl ServiceManager {
 
         let guard =
             Subreaper::track_child(process.id()).wrap_err("Failed to track service child")?;
+        Subreaper::track_group(process.id(), &self.orphans);
 
         Ok((process, guard))
     }
@@ -0,0 +1,137 @@
+//! Attributes Module
+//!
+//! Applies the process attributes of a service, like resource limits and
+//! scheduling priorities, in the child process before it runs the service
+
+use std::io;
+
+use nix::sys::resource::{Resource, setrlimit};
+use tokio::process::Command;
+
+use crate::process_manager::service::{IoSchedulingClass, Limit, Process};
+
+/// Process attributes of a service, prepared to be applied in the child
+///
+/// Everything is computed up front, as only async-signal-safe functions may
+/// be called between forking and running the service binary
+pub struct Attributes {
+    limits: Vec<(Resource, u64)>,
+    nice: Option<i32>,
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    io_priority: Option<i32>,
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    oom_score_adjust: Option<String>,
+    umask: Option<u32>,
+}
+
+impl Attributes {
+    /// Collect the attributes set for a service process
+    pub fn new(process: &Process) -> Self {
+        let limits = [
+            (Resource::RLIMIT_NOFILE, process.limits.nofile),
+            (Resource::RLIMIT_CORE, process.limits.core),
+            (Resource::RLIMIT_NPROC, process.limits.nproc),
+            (Resource::RLIMIT_AS, process.limits.address_space),
+        ]
+        .into_iter()
+        .filter_map(|(resource, limit)| limit.map(|Limit(limit)| (resource, limit)))
+        .collect();
+
+        // Matches the kernel's `IOPRIO_PRIO_VALUE`, with the default priority
+        // of 4 when only a class is given
+        let io_priority = match (process.io_scheduling_class, process.io_scheduling_priority) {
+            (None, None) => None,
+            (Some(IoSchedulingClass::Idle), _) => Some(3 << 13),
+            (class, priority) => {
+                let class = match class {
+                    Some(IoSchedulingClass::Realtime) => 1,
+                    _ => 2,
+                };
+                Some(class << 13 | i32::from(priority.unwrap_or(4)))
+            }
+        };
+
+        Self {
+            limits,
+            nice: process.nice,
+            io_priority,
+            oom_score_adjust: process.oom_score_adjust.map(|adjust| adjust.to_string()),
+            umask: process.umask.map(|umask| umask.0),
+        }
+    }
+
+    /// Make `command` apply the attributes to the process it spawns
+    ///
+    /// Failing to apply any of them fails spawning the process, e.g. when
+    /// nimi lacks the privileges to raise a hard limit or lower the nice value
+    pub fn apply(self, command: &mut Command) {
+        // SAFETY: `apply_in_child` only makes async-signal-safe system calls
+        // and doesn't allocate
+        unsafe {
+            command.pre_exec(move || self.apply_in_child());
+        }
+    }
+
+    fn apply_in_child(&self) -> io::Result<()> {
+        for &(resource, limit) in &self.limits {
+            setrlimit(resource, limit, limit)?;
+        }
+
+        if let Some(nice) = self.nice {
+            // SAFETY: Only changes the priority of the calling process
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        if let Some(umask) = self.umask {
+            // SAFETY: `umask` can't fail
+            unsafe { libc::umask(umask as libc::mode_t) };
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+
+            if let Some(priority) = self.io_priority {
+                // SAFETY: Only changes the I/O priority of the calling process
+                let ret =
+                    unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, priority) };
+                if ret < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+
+            if let Some(adjust) = &self.oom_score_adjust {
+                Self::write_oom_score_adjust(adjust)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write to `/proc/self/oom_score_adj` without allocating
+    #[cfg(target_os = "linux")]
+    fn write_oom_score_adjust(adjust: &str) -> io::Result<()> {
+        // SAFETY: The path is a valid C string and the fd is closed on every path
+        unsafe {
+            let fd = libc::open(
+                c"/proc/self/oom_score_adj".as_ptr(),
+                libc::O_WRONLY | libc::O_CLOEXEC,
+            );
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let written = libc::write(fd, adjust.as_ptr().cast(), adjust.len());
+            let result = if written < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            };
+            libc::close(fd);
+
+            result
+        }
+    }
+}
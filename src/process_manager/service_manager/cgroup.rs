@@ -0,0 +1,251 @@
+//! Cgroup Module
+//!
+//! Places every service in its own cgroup v2 below the delegated cgroup nimi
+//! was started in, to limit its resources and stop all of its processes
+
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    os::fd::{AsRawFd, OwnedFd},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use eyre::{Context, OptionExt, Result};
+use nix::sys::signal::{Signal, kill};
+use nix::unistd::Pid;
+use tokio::process::Command;
+
+use crate::process_manager::service::Process;
+
+/// Controllers nimi applies limits for, if the delegated cgroup has them
+const CONTROLLERS: [&str; 3] = ["memory", "cpu", "pids"];
+
+/// Cgroup of a single service
+pub struct Cgroup(PathBuf);
+
+/// Resources a service used over all of its runs
+pub struct Usage {
+    /// CPU time spent in user and system mode
+    pub cpu: Option<Duration>,
+
+    /// Highest memory usage in bytes
+    pub memory_peak: Option<u64>,
+}
+
+impl Cgroup {
+    /// Prepare the cgroup nimi was started in for holding one cgroup per service
+    ///
+    /// Controllers can only be enabled for the children of a cgroup which
+    /// doesn't contain processes itself, so nimi first moves into the
+    /// `nimi.scope` child. Returns the path of the cgroup nimi was started in.
+    pub fn setup_root() -> Result<PathBuf> {
+        let root = Self::own_path().wrap_err("Failed to find the cgroup nimi runs in")?;
+
+        let leaf = root.join("nimi.scope");
+        Self::create_dir(&leaf)?;
+        fs::write(leaf.join("cgroup.procs"), "0")
+            .wrap_err_with(|| format!("Failed to move nimi into {}", leaf.display()))?;
+
+        let available = fs::read_to_string(root.join("cgroup.controllers"))
+            .wrap_err("Failed to read the available cgroup controllers")?;
+        let enable: Vec<_> = available
+            .split_whitespace()
+            .filter(|controller| CONTROLLERS.contains(controller))
+            .map(|controller| format!("+{controller}"))
+            .collect();
+
+        if !enable.is_empty() {
+            fs::write(root.join("cgroup.subtree_control"), enable.join(" ")).wrap_err_with(
+                || {
+                    format!(
+                        "Failed to enable cgroup controllers in {}, it must be delegated to nimi and contain no other processes",
+                        root.display()
+                    )
+                },
+            )?;
+        }
+
+        Ok(root)
+    }
+
+    /// Create the cgroup of a service below `root` and apply its limits
+    pub fn new(root: &Path, name: &str, process: &Process) -> Result<Self> {
+        let cgroup = Self(root.join(format!("{name}.service")));
+        Self::create_dir(&cgroup.0)?;
+
+        let limits = [
+            ("memory.max", process.memory_max.clone()),
+            ("cpu.max", process.cpu_max.clone()),
+            ("pids.max", process.pids_max.map(|max| max.to_string())),
+        ];
+
+        for (file, limit) in limits {
+            let Some(limit) = limit else {
+                continue;
+            };
+
+            fs::write(cgroup.0.join(file), &limit).wrap_err_with(|| {
+                format!(
+                    "Failed to set {file} of service {name:?} to {limit:?}, is the {} controller delegated to nimi?",
+                    file.trim_end_matches(".max")
+                )
+            })?;
+        }
+
+        Ok(cgroup)
+    }
+
+    /// Make `command` move the process it spawns into this cgroup
+    ///
+    /// The process joins the cgroup before running the service binary, so
+    /// everything it forks ends up in the cgroup as well. The returned file
+    /// has to be kept open until the process is spawned.
+    pub fn place(&self, command: &mut Command) -> Result<OwnedFd> {
+        let procs: OwnedFd = fs::OpenOptions::new()
+            .write(true)
+            .open(self.0.join("cgroup.procs"))
+            .wrap_err_with(|| format!("Failed to open {}", self.0.display()))?
+            .into();
+        let fd = procs.as_raw_fd();
+
+        // SAFETY: `write` is async-signal-safe, and `fd` stays open until the
+        // process is spawned
+        unsafe {
+            command.pre_exec(move || {
+                if libc::write(fd, b"0".as_ptr().cast(), 1) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        Ok(procs)
+    }
+
+    /// Send a signal to every process in the cgroup
+    pub fn kill(&self, signal: Signal) {
+        if signal == Signal::SIGKILL && fs::write(self.0.join("cgroup.kill"), "1").is_ok() {
+            return;
+        }
+
+        // `cgroup.kill` needs Linux 5.14, and only ever sends `SIGKILL`
+        for pid in self.procs() {
+            let _ = kill(pid, signal);
+        }
+    }
+
+    /// Check if any process is left in the cgroup
+    pub fn is_populated(&self) -> bool {
+        fs::read_to_string(self.0.join("cgroup.events"))
+            .map(|events| events.lines().any(|line| line == "populated 1"))
+            .unwrap_or(false)
+    }
+
+    /// Check if `pid` is in the cgroup
+    pub fn contains(&self, pid: Pid) -> bool {
+        self.procs().contains(&pid)
+    }
+
+    /// Get the resources used by the processes in the cgroup so far
+    pub fn usage(&self) -> Usage {
+        let cpu = fs::read_to_string(self.0.join("cpu.stat"))
+            .ok()
+            .and_then(|stat| {
+                stat.lines()
+                    .find_map(|line| line.strip_prefix("usage_usec "))
+                    .and_then(|usec| usec.parse().ok())
+            })
+            .map(Duration::from_micros);
+
+        let memory_peak = fs::read_to_string(self.0.join("memory.peak"))
+            .ok()
+            .and_then(|peak| peak.trim().parse().ok());
+
+        Usage { cpu, memory_peak }
+    }
+
+    /// Remove the cgroup, killing the processes which are still left in it
+    ///
+    /// Waits up to `timeout_duration` for them to exit, as a populated cgroup
+    /// can't be removed
+    pub async fn remove(&self, timeout_duration: Duration) -> Result<()> {
+        if self.is_populated() {
+            self.kill(Signal::SIGKILL);
+
+            let emptied = async {
+                while self.is_populated() {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+            };
+            tokio::time::timeout(timeout_duration, emptied)
+                .await
+                .wrap_err_with(|| format!("Processes are still left in {}", self.0.display()))?;
+        }
+
+        fs::remove_dir(&self.0).wrap_err_with(|| format!("Failed to remove {}", self.0.display()))
+    }
+
+    fn procs(&self) -> Vec<Pid> {
+        fs::read_to_string(self.0.join("cgroup.procs"))
+            .unwrap_or_default()
+            .split_whitespace()
+            .filter_map(|pid| pid.parse().ok())
+            .map(Pid::from_raw)
+            .collect()
+    }
+
+    fn create_dir(path: &Path) -> Result<()> {
+        match fs::create_dir(path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(()),
+            Err(e) => {
+                Err(e).wrap_err_with(|| format!("Failed to create cgroup {}", path.display()))
+            }
+        }
+    }
+
+    /// Get the directory of the cgroup
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Find the directory of the cgroup a process is in, zombies included
+    pub fn path_of(pid: Pid) -> Result<PathBuf> {
+        Self::find_path(&format!("/proc/{pid}/cgroup"))
+    }
+
+    /// Find the directory of the cgroup nimi runs in
+    fn own_path() -> Result<PathBuf> {
+        Self::find_path("/proc/self/cgroup")
+    }
+
+    /// Find the directory of the cgroup listed in the `cgroups` file of a process
+    ///
+    /// Looks for the cgroup2 mount instead of assuming `/sys/fs/cgroup`, so
+    /// hybrid hierarchies mounting it at `/sys/fs/cgroup/unified` work too
+    fn find_path(cgroups: &str) -> Result<PathBuf> {
+        let cgroups = fs::read_to_string(cgroups)?;
+        let own = cgroups
+            .lines()
+            .find_map(|line| line.strip_prefix("0::"))
+            .ok_or_eyre("nimi isn't part of a cgroup v2 hierarchy")?;
+
+        let mounts = fs::read_to_string("/proc/self/mountinfo")?;
+        let (mount_root, mount_point) = mounts
+            .lines()
+            .find_map(|line| {
+                let (mount, fs_type) = line.split_once(" - ")?;
+                if !fs_type.starts_with("cgroup2 ") {
+                    return None;
+                }
+
+                let mut fields = mount.split_whitespace().skip(3);
+                Some((fields.next()?, fields.next()?))
+            })
+            .ok_or_eyre("No cgroup2 filesystem is mounted")?;
+
+        let relative = own.strip_prefix(mount_root).unwrap_or(own);
+        Ok(Path::new(mount_point).join(relative.trim_start_matches('/')))
+    }
+}
@@ -0,0 +1,191 @@
+//! Notify Socket Module
+//!
+//! Implements the receiving end of systemd's [`sd_notify`](https://www.freedesktop.org/software/systemd/man/latest/sd_notify.html)
+//! protocol, so services built for `Type=notify` can report readiness and pet a watchdog
+
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use eyre::{Context, Result};
+use tokio::{
+    net::UnixDatagram,
+    time::{Instant, sleep_until},
+};
+
+/// Largest datagram accepted on the notify socket
+const MAX_MESSAGE_LEN: usize = 4096;
+
+/// A single state change sent by a service over its notify socket
+#[derive(Debug, PartialEq, Eq)]
+pub enum NotifyMessage {
+    /// `READY=1`, the service finished starting up
+    Ready,
+
+    /// `STOPPING=1`, the service is shutting down
+    Stopping,
+
+    /// `STATUS=...`, free form status text
+    Status(String),
+
+    /// `WATCHDOG=1`, resets the watchdog timer
+    Watchdog,
+
+    /// `WATCHDOG=trigger`, the service asks to be treated as hung
+    WatchdogTrigger,
+
+    /// `MAINPID=...`, the pid of the main process of the service
+    MainPid(u32),
+}
+
+impl NotifyMessage {
+    /// Parse the newline separated assignments of a notify datagram
+    ///
+    /// Unknown or malformed assignments are skipped
+    pub fn parse(datagram: &str) -> Vec<Self> {
+        datagram
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .filter_map(|assignment| match assignment {
+                ("READY", "1") => Some(Self::Ready),
+                ("STOPPING", "1") => Some(Self::Stopping),
+                ("STATUS", status) => Some(Self::Status(status.to_owned())),
+                ("WATCHDOG", "1") => Some(Self::Watchdog),
+                ("WATCHDOG", "trigger") => Some(Self::WatchdogTrigger),
+                ("MAINPID", pid) => pid.parse().ok().map(Self::MainPid),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Per service datagram socket exported to the service as `NOTIFY_SOCKET`
+///
+/// The socket file is removed again when this is dropped
+pub struct NotifySocket {
+    socket: UnixDatagram,
+    path: PathBuf,
+}
+
+impl NotifySocket {
+    /// Bind a notify socket for the given service inside `tmp_dir`
+    pub fn bind(tmp_dir: &Path, name: &str) -> Result<Self> {
+        let path = tmp_dir.join(format!("nimi-notify-{}-{}.sock", std::process::id(), name));
+
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e).wrap_err("Failed to remove stale notify socket"),
+        }
+
+        let socket = UnixDatagram::bind(&path)
+            .wrap_err_with(|| format!("Failed to bind notify socket at {path:?}"))?;
+
+        Ok(Self { socket, path })
+    }
+
+    /// Path of the socket, to be exported as `NOTIFY_SOCKET`
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Send `state` to the supervisor of nimi itself through its `NOTIFY_SOCKET`
+    ///
+    /// Does nothing when nimi wasn't given a notify socket, e.g. when it isn't
+    /// run by systemd with `Type=notify`
+    pub fn notify_supervisor(state: &str) -> Result<()> {
+        let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+            return Ok(());
+        };
+
+        let socket = std::os::unix::net::UnixDatagram::unbound()
+            .wrap_err("Failed to create a socket for notifying the supervisor")?;
+
+        // Sockets starting with `@` live in the abstract namespace
+        #[cfg(target_os = "linux")]
+        if let Some(name) = path.as_encoded_bytes().strip_prefix(b"@") {
+            use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+
+            let addr = SocketAddr::from_abstract_name(name)?;
+            socket
+                .send_to_addr(state.as_bytes(), &addr)
+                .wrap_err("Failed to notify the supervisor")?;
+            return Ok(());
+        }
+
+        socket
+            .send_to(state.as_bytes(), &path)
+            .wrap_err("Failed to notify the supervisor")?;
+
+        Ok(())
+    }
+
+    /// Drop every datagram which was sent to the socket so far
+    ///
+    /// Returns how many were dropped
+    pub fn drain(&self) -> usize {
+        let mut buf = [0; MAX_MESSAGE_LEN];
+        let mut dropped = 0;
+        while self.socket.try_recv(&mut buf).is_ok() {
+            dropped += 1;
+        }
+
+        dropped
+    }
+
+    /// Receive the messages of the next datagram sent to the socket
+    pub async fn recv(&self) -> Result<Vec<NotifyMessage>> {
+        let mut buf = [0; MAX_MESSAGE_LEN];
+        let len = self
+            .socket
+            .recv(&mut buf)
+            .await
+            .wrap_err("Failed to receive from notify socket")?;
+
+        Ok(NotifyMessage::parse(&String::from_utf8_lossy(&buf[..len])))
+    }
+}
+
+impl Drop for NotifySocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Tracks the deadline by which a service has to send `WATCHDOG=1`
+pub struct Watchdog {
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+}
+
+impl Watchdog {
+    /// Create an unarmed watchdog, `None` disables it entirely
+    pub fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            timeout,
+            deadline: None,
+        }
+    }
+
+    /// Arm the watchdog, or push its deadline back by a full timeout
+    pub fn reset(&mut self) {
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    /// Expire the watchdog immediately
+    pub fn trigger(&mut self) {
+        self.deadline = Some(Instant::now());
+    }
+
+    /// Resolves once the deadline has passed
+    ///
+    /// Never resolves while the watchdog is unarmed
+    pub async fn expired(&self) {
+        match self.deadline {
+            Some(deadline) => sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! Process Tree Module
+//!
+//! Finds the descendants of a process through `/proc`, so processes which left
+//! the process group of a service can still be killed
+
+use std::{fs, str::FromStr};
+
+use nix::sys::signal::{Signal, kill};
+use nix::unistd::Pid;
+
+/// Snapshot of the descendants of a process
+///
+/// Each pid is stored with its start time, so a pid which got reused by an
+/// unrelated process in the meantime is never signalled
+pub struct Descendants(Vec<(Pid, u64)>);
+
+/// A single process, remembered with its start time for the same reason
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessId(Pid, u64);
+
+impl ProcessId {
+    /// Remember a running process, `None` if it doesn't exist
+    pub fn new(pid: Pid) -> Option<Self> {
+        Descendants::start_time(pid).map(|start_time| Self(pid, start_time))
+    }
+
+    /// Get the pid of the process, `None` once it exited
+    pub fn pid(&self) -> Option<Pid> {
+        (Descendants::start_time(self.0) == Some(self.1)).then_some(self.0)
+    }
+
+    /// Get the pid of the parent of the process
+    pub fn parent(&self) -> Option<Pid> {
+        Descendants::stat_field(self.pid()?, 1).map(Pid::from_raw)
+    }
+}
+
+impl Descendants {
+    /// Collect every descendant of `pid`
+    ///
+    /// Empty if `/proc` isn't mounted or the kernel lacks `CONFIG_PROC_CHILDREN`
+    pub fn of(pid: Pid) -> Self {
+        let mut found = Vec::new();
+        let mut queue = vec![pid];
+
+        while let Some(parent) = queue.pop() {
+            for child in Self::children(parent) {
+                if let Some(start_time) = Self::start_time(child) {
+                    found.push((child, start_time));
+                    queue.push(child);
+                }
+            }
+        }
+
+        Self(found)
+    }
+
+    /// Add a process and every one of its descendants
+    pub fn add(&mut self, process: ProcessId) {
+        if let Some(pid) = process.pid() {
+            self.0.push((process.0, process.1));
+            self.0.extend(Self::of(pid).0);
+        }
+    }
+
+    /// Check if `pid` is one of the descendants
+    pub fn contains(&self, pid: Pid) -> bool {
+        self.0.iter().any(|(descendant, _)| *descendant == pid)
+    }
+
+    /// Send a signal to every descendant which is still alive
+    pub fn kill(&self, signal: Signal) {
+        for &pid in self.alive() {
+            let _ = kill(pid, signal);
+        }
+    }
+
+    /// Check if any of the descendants is still alive
+    pub fn any_alive(&self) -> bool {
+        self.alive().next().is_some()
+    }
+
+    fn alive(&self) -> impl Iterator<Item = &Pid> {
+        self.0
+            .iter()
+            .filter(|(pid, start_time)| Self::start_time(*pid) == Some(*start_time))
+            .map(|(pid, _)| pid)
+    }
+
+    /// Get the direct children of every thread of a process
+    fn children(pid: Pid) -> Vec<Pid> {
+        let Ok(tasks) = fs::read_dir(format!("/proc/{pid}/task")) else {
+            return Vec::new();
+        };
+
+        tasks
+            .flatten()
+            .filter_map(|task| fs::read_to_string(task.path().join("children")).ok())
+            .flat_map(|children| {
+                children
+                    .split_whitespace()
+                    .filter_map(|child| child.parse().ok())
+                    .map(Pid::from_raw)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Get the start time of a process, in clock ticks after boot
+    ///
+    /// `starttime` is the 22nd field of `/proc/<pid>/stat`
+    fn start_time(pid: Pid) -> Option<u64> {
+        Self::stat_field(pid, 19)
+    }
+
+    /// Parse the `n`th field after the command name in `/proc/<pid>/stat`
+    fn stat_field<T: FromStr>(pid: Pid, n: usize) -> Option<T> {
+        let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+
+        // The command name may contain spaces and parentheses, so skip past its
+        // closing parenthesis
+        let (_, fields) = stat.rsplit_once(')')?;
+        fields.split_whitespace().nth(n)?.parse().ok()
+    }
+}
@@ -0,0 +1,141 @@
+//! Service State Module
+//!
+//! Shares the lifecycle state of each service with the other services that depend on it
+
+use std::sync::Arc;
+
+use futures::future::select_all;
+use tokio::sync::watch;
+
+/// Lifecycle state of a service
+///
+/// Published by the `ServiceManager` of the service through a `watch` channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceState {
+    /// Waiting on dependencies before starting
+    Pending,
+
+    /// The process has been spawned, but isn't ready yet
+    Started,
+
+    /// The process is running and its readiness probe (if any) succeeded
+    Ready,
+
+    /// The oneshot service ran to completion and won't be started again
+    Completed,
+
+    /// The service has stopped and won't be started again
+    Stopped,
+
+    /// The service failed and won't be started again
+    Failed,
+}
+
+impl ServiceState {
+    /// If the service has finished and won't change state again
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Completed | Self::Stopped | Self::Failed)
+    }
+
+    /// Wait for every given service to become ready, or complete for oneshot services
+    ///
+    /// Waits until all of them settled, returning `false` if any of them
+    /// stopped before becoming ready
+    pub async fn wait_for_ready(states: &mut [watch::Receiver<ServiceState>]) -> bool {
+        let mut all_ready = true;
+
+        for state in states {
+            match state
+                .wait_for(|state| *state == Self::Ready || state.is_terminal())
+                .await
+            {
+                Ok(state) if matches!(*state, Self::Ready | Self::Completed) => {}
+                _ => all_ready = false,
+            }
+        }
+
+        all_ready
+    }
+
+    /// Wait for every given service to reach a terminal state
+    ///
+    /// Used so services get stopped in the reverse order they were started in
+    pub async fn wait_for_terminal(states: &mut [watch::Receiver<ServiceState>]) {
+        for state in states {
+            let _ = state.wait_for(Self::is_terminal).await;
+        }
+    }
+}
+
+/// A service which has to be started before the service depending on it
+pub struct Dependency {
+    /// Name of the dependency
+    pub name: Arc<String>,
+
+    /// If the dependent service should fail when this dependency fails
+    pub required: bool,
+
+    /// State of the dependency
+    pub state: watch::Receiver<ServiceState>,
+}
+
+impl Dependency {
+    /// Wait until the dependency reaches a state matching `f`
+    ///
+    /// If the `ServiceManager` of the dependency goes away without reaching a
+    /// terminal state it is treated as failed.
+    pub async fn settled(&mut self, f: impl FnMut(&ServiceState) -> bool) -> ServiceState {
+        if let Ok(state) = self.state.wait_for(f).await {
+            return *state;
+        }
+
+        match *self.state.borrow() {
+            state if state.is_terminal() => state,
+            _ => ServiceState::Failed,
+        }
+    }
+
+    /// Wait for every dependency to become ready
+    ///
+    /// Returns the name of the first required dependency which failed to start
+    pub async fn wait_for_all(dependencies: &mut [Dependency]) -> Option<Arc<String>> {
+        for dependency in dependencies {
+            let state = dependency
+                .settled(|state| *state == ServiceState::Ready || state.is_terminal())
+                .await;
+
+            if dependency.required && state == ServiceState::Failed {
+                return Some(Arc::clone(&dependency.name));
+            }
+        }
+
+        None
+    }
+
+    /// Wait for any required dependency to fail
+    ///
+    /// Never resolves if no required dependency fails
+    pub async fn wait_for_required_failure(dependencies: &mut [Dependency]) -> Arc<String> {
+        let failures: Vec<_> = dependencies
+            .iter_mut()
+            .filter(|dependency| dependency.required)
+            .map(|dependency| {
+                Box::pin(async move {
+                    match dependency
+                        .settled(|state| *state == ServiceState::Failed)
+                        .await
+                    {
+                        ServiceState::Failed => Arc::clone(&dependency.name),
+                        _ => std::future::pending().await,
+                    }
+                })
+            })
+            .collect();
+
+        if failures.is_empty() {
+            return std::future::pending().await;
+        }
+
+        select_all(failures).await.0
+    }
+}
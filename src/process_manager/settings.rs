@@ -8,35 +8,44 @@ use std::time::Duration;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_with::serde_as;
 
+use crate::process_manager::{Step, service::ExitKind};
+
 /// Settings Struct
 ///
 /// Process manager runtime settings for configuring things like restart behaviour
+#[serde_as]
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Settings {
     /// The restart specific settings
     pub restart: Restart,
 
+    /// The amount of time (in milliseconds) a process gets to exit after
+    /// being asked to stop, before it is killed
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    #[serde(rename = "stopTimeout", default = "default_stop_timeout")]
+    pub stop_timeout: Duration,
+
     /// The startup specific settings
     pub startup: Startup,
 
+    /// The shutdown specific settings
+    #[serde(default)]
+    pub shutdown: Shutdown,
+
     /// The logging specific settings
     pub logging: Logging,
+
+    /// The exit code specific settings
+    #[serde(default)]
+    pub exit: Exit,
+
+    /// The cgroup specific settings
+    #[serde(default)]
+    pub cgroups: Cgroups,
 }
 
-impl Settings {
-    /// Convert `RestartMode` into
-    /// mprocs' less semantically clear
-    /// "should this restart".
-    ///
-    /// `RestartMode::UpToCount` is unclear so it becomes
-    /// "this should restart"
-    pub fn autorestart(&self) -> bool {
-        match self.restart.mode {
-            RestartMode::Always => true,
-            RestartMode::UpToCount => true,
-            RestartMode::Never => false,
-        }
-    }
+fn default_stop_timeout() -> Duration {
+    Duration::from_secs(1)
 }
 
 /// Startup Settings Struct
@@ -47,6 +56,59 @@ pub struct Startup {
     /// Binary to run on startup before starting services
     #[serde(rename = "runOnStartup")]
     pub run_on_startup: Option<String>,
+
+    /// Steps to run in order on startup before starting services
+    #[serde(default)]
+    pub steps: Vec<Step>,
+}
+
+/// Shutdown Settings Struct
+///
+/// Configuration for what nimi does after every service stopped
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Shutdown {
+    /// Steps to run in order once every service stopped, before nimi exits
+    #[serde(default)]
+    pub steps: Vec<Step>,
+}
+
+/// Cgroups Settings Struct
+///
+/// Configuration for placing services in their own cgroup v2
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Cgroups {
+    /// Create a cgroup per service below the delegated cgroup nimi runs in
+    #[serde(default)]
+    pub enable: bool,
+}
+
+/// Exit Settings Struct
+///
+/// Configuration for the exit code nimi reports
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Exit {
+    /// Exit codes which are reported as `0` instead
+    #[serde(rename = "remapToSuccess", default)]
+    pub remap_to_success: Vec<u8>,
+
+    /// Stop every service as soon as any service exits
+    #[serde(rename = "abortOnExit", default)]
+    pub abort_on_exit: bool,
+
+    /// Service whose exit stops every service and decides the exit code
+    #[serde(rename = "codeFrom", default)]
+    pub code_from: Option<String>,
+}
+
+impl Exit {
+    /// Apply `remap_to_success` to an exit code
+    pub fn remap(&self, code: u8) -> u8 {
+        if self.remap_to_success.contains(&code) {
+            0
+        } else {
+            code
+        }
+    }
 }
 
 /// Logging Settings Struct
@@ -90,11 +152,15 @@ struct LoggingRaw {
 ///
 /// Configuration for how nimi gets restarted
 #[serde_as]
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Restart {
     /// The mode to use for restarts
     pub mode: RestartMode,
 
+    /// Which kinds of exits trigger a restart
+    #[serde(default)]
+    pub condition: RestartCondition,
+
     /// The amount of time (in milliseconds) to wait before
     /// restarting the process
     #[serde_as(as = "DurationMilliSeconds<u64>")]
@@ -102,12 +168,130 @@ pub struct Restart {
 
     /// The maximum amount of restarts in `RestartMode::UpToCount`
     pub count: usize,
+
+    /// Exponential backoff applied to `time` for consecutive restarts
+    #[serde(default)]
+    pub backoff: Option<Backoff>,
+
+    /// How long (in milliseconds) a service has to stay up
+    /// before its restart count is reset
+    #[serde_as(as = "Option<DurationMilliSeconds<u64>>")]
+    #[serde(rename = "resetAfter", default)]
+    pub reset_after: Option<Duration>,
+
+    /// Limits how many restarts may happen within a time window
+    #[serde(default)]
+    pub limit: Option<RestartLimit>,
+}
+
+impl Restart {
+    /// Apply the per service overrides on top of these settings
+    pub fn with_overrides(&self, overrides: &RestartOverrides) -> Self {
+        Self {
+            mode: overrides.mode.unwrap_or(self.mode),
+            condition: overrides.condition.unwrap_or(self.condition),
+            time: overrides.time.unwrap_or(self.time),
+            count: overrides.count.unwrap_or(self.count),
+            limit: overrides.limit.or(self.limit),
+            ..self.clone()
+        }
+    }
+
+    /// Convert `RestartMode` into
+    /// mprocs' less semantically clear
+    /// "should this restart".
+    ///
+    /// `RestartMode::UpToCount` is unclear so it becomes
+    /// "this should restart"
+    pub fn autorestart(&self) -> bool {
+        match self.mode {
+            RestartMode::Always => true,
+            RestartMode::UpToCount => true,
+            RestartMode::Never => false,
+        }
+    }
+
+    /// Get the delay before restarting a service which
+    /// has already been restarted `restarts` times
+    pub fn delay(&self, restarts: usize) -> Duration {
+        let Some(backoff) = &self.backoff else {
+            return self.time;
+        };
+
+        let max = backoff.max_time.as_secs_f64();
+        let exponent = i32::try_from(restarts).unwrap_or(i32::MAX);
+        let delay = (self.time.as_secs_f64() * backoff.multiplier.powi(exponent)).min(max);
+        let jitter = delay * backoff.jitter * (fastrand::f64() * 2.0 - 1.0);
+
+        // Extreme multipliers make the delay infinite or NaN, so wait the longest
+        Duration::try_from_secs_f64((delay + jitter).clamp(0.0, max)).unwrap_or(backoff.max_time)
+    }
+}
+
+/// Restart Overrides Struct
+///
+/// Per service replacements for the global restart settings
+#[serde_as]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RestartOverrides {
+    /// Replaces `Restart::mode`
+    #[serde(default)]
+    pub mode: Option<RestartMode>,
+
+    /// Replaces `Restart::condition`
+    #[serde(default)]
+    pub condition: Option<RestartCondition>,
+
+    /// Replaces `Restart::time`
+    #[serde_as(as = "Option<DurationMilliSeconds<u64>>")]
+    #[serde(default)]
+    pub time: Option<Duration>,
+
+    /// Replaces `Restart::count`
+    #[serde(default)]
+    pub count: Option<usize>,
+
+    /// Replaces `Restart::limit`
+    #[serde(default)]
+    pub limit: Option<RestartLimit>,
+}
+
+/// Restart Limit Settings Struct
+///
+/// Allows at most `burst` restarts within any `interval`
+#[serde_as]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RestartLimit {
+    /// Number of restarts allowed within `interval`
+    pub burst: usize,
+
+    /// Length (in milliseconds) of the sliding window restarts are counted in
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    pub interval: Duration,
+}
+
+/// Restart Backoff Settings Struct
+///
+/// Grows the restart delay for services which keep failing
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backoff {
+    /// Factor the delay grows by with every consecutive restart
+    pub multiplier: f64,
+
+    /// The upper bound (in milliseconds) for the delay
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    #[serde(rename = "maxTime")]
+    pub max_time: Duration,
+
+    /// Fraction of the delay to randomly add or subtract
+    pub jitter: f64,
 }
 
 /// Restart Mode
 ///
 /// Selects how the processes get restarted on failure
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
 pub enum RestartMode {
     /// Don't restart, ever
     #[default]
@@ -122,3 +306,36 @@ pub enum RestartMode {
     #[serde(rename = "always")]
     Always,
 }
+
+/// Selects which kinds of exits trigger a restart
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub enum RestartCondition {
+    /// Restart after every exit
+    #[serde(rename = "always")]
+    Always,
+
+    /// Restart only after clean exits
+    #[serde(rename = "on-success")]
+    OnSuccess,
+
+    /// Restart after unclean exit codes and abnormal exits
+    #[default]
+    #[serde(rename = "on-failure")]
+    OnFailure,
+
+    /// Restart only after abnormal exits
+    #[serde(rename = "on-abnormal")]
+    OnAbnormal,
+}
+
+impl RestartCondition {
+    /// Check if a process which ended in `exit` should be restarted
+    pub fn restarts_on(&self, exit: ExitKind) -> bool {
+        match self {
+            Self::Always => true,
+            Self::OnSuccess => exit == ExitKind::Success,
+            Self::OnFailure => exit != ExitKind::Success,
+            Self::OnAbnormal => exit == ExitKind::Abnormal,
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Step Module
+//!
+//! One-off commands which run to completion outside of a service, such as startup steps
+
+use std::{
+    collections::HashMap, ffi::OsString, path::PathBuf, process::Stdio, sync::Arc, time::Duration,
+};
+
+use eyre::{Context, Result};
+use log::{debug, warn};
+use nix::sys::signal::Signal;
+use serde::{Deserialize, Serialize};
+use serde_with::{DurationMilliSeconds, serde_as};
+use tokio::{process::Command, task::JoinSet};
+use tokio_util::sync::CancellationToken;
+
+use crate::process_manager::ServiceManager;
+use crate::process_manager::service::ArgV;
+use crate::process_manager::service_manager::{Logger, ServiceError};
+use crate::subreaper::{Orphans, Subreaper};
+
+/// Step Struct
+///
+/// A command which has to exit successfully, optionally retried when it fails
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Step {
+    /// Argv used to run the step
+    pub argv: ArgV,
+
+    /// How long (in milliseconds) a single attempt may take
+    #[serde_as(as = "Option<DurationMilliSeconds<u64>>")]
+    #[serde(default)]
+    pub timeout: Option<Duration>,
+
+    /// How often to retry the step after a failed attempt
+    #[serde(default)]
+    pub retries: usize,
+
+    /// Environment variables set for the step
+    #[serde(default)]
+    pub environment: HashMap<String, String>,
+}
+
+/// Environment a step runs in, before its own `environment` is applied
+#[derive(Debug, Default, Clone)]
+pub struct StepEnv {
+    /// If the environment inherited from nimi is cleared first
+    pub clear: bool,
+
+    /// Variables set on top of the inherited environment
+    pub vars: Vec<(OsString, OsString)>,
+}
+
+impl Step {
+    /// Create a step running a single binary without arguments
+    pub fn from_binary(bin: &str) -> Result<Self> {
+        Ok(Self {
+            argv: vec![bin.to_owned()].try_into()?,
+            timeout: None,
+            retries: 0,
+            environment: HashMap::new(),
+        })
+    }
+
+    /// Run the step until it succeeds or runs out of retries
+    ///
+    /// Output is logged under `target`. If `cancel_tok` is cancelled the step is
+    /// stopped, waiting up to `stop_timeout` before killing it, and counts as done.
+    ///
+    /// `env` is applied to the inherited environment, and can be overridden by
+    /// the step's own `environment`.
+    pub async fn run(
+        &self,
+        target: &Arc<String>,
+        logs_dir: &Arc<Option<PathBuf>>,
+        cancel_tok: &CancellationToken,
+        stop_timeout: Duration,
+        env: &StepEnv,
+    ) -> Result<()> {
+        let mut attempt = 0;
+
+        loop {
+            match self
+                .run_once(target, logs_dir, cancel_tok, stop_timeout, env)
+                .await
+            {
+                Err(e) if attempt < self.retries && !cancel_tok.is_cancelled() => {
+                    attempt += 1;
+                    warn!(target: target, "Step failed, retrying ({attempt}/{}): {e}", self.retries);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn run_once(
+        &self,
+        target: &Arc<String>,
+        logs_dir: &Arc<Option<PathBuf>>,
+        cancel_tok: &CancellationToken,
+        stop_timeout: Duration,
+        env: &StepEnv,
+    ) -> Result<()> {
+        let mut set = JoinSet::new();
+
+        let (mut process, child_guard) = {
+            let mut command = Command::new(self.argv.binary());
+            if env.clear {
+                command.env_clear();
+            }
+
+            let _pause = Subreaper::pause_reaping();
+            let process = command
+                .args(self.argv.args())
+                .env_remove("NOTIFY_SOCKET")
+                .envs(env.vars.iter().map(|(key, value)| (key, value)))
+                .envs(&self.environment)
+                .process_group(0)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()
+                .wrap_err_with(|| format!("Failed to spawn step: {:?}", self.argv))?;
+            let guard =
+                Subreaper::track_child(process.id()).wrap_err("Failed to track step child")?;
+            Subreaper::track_group(process.id(), &Orphans::new(Arc::clone(target)));
+
+            (process, guard)
+        };
+
+        // Gone once the process was waited on
+        let pgid = process.id();
+
+        Logger::Stdout.start(
+            &mut process.stdout,
+            Arc::clone(target),
+            Arc::clone(logs_dir),
+            &mut set,
+        )?;
+        Logger::Stderr.start(
+            &mut process.stderr,
+            Arc::clone(target),
+            Arc::clone(logs_dir),
+            &mut set,
+        )?;
+
+        let timed_out = async {
+            match self.timeout {
+                Some(timeout) => {
+                    tokio::time::sleep(timeout).await;
+                    timeout
+                }
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            _ = cancel_tok.cancelled() => {
+                debug!(target: target, "Received shutdown signal");
+                ServiceManager::shutdown_process(&mut process, &[Signal::SIGTERM], stop_timeout, None, None).await?;
+            }
+            timeout = timed_out => {
+                ServiceManager::shutdown_process(&mut process, &[Signal::SIGTERM], stop_timeout, None, None).await?;
+                eyre::bail!(ServiceError::StepTimedOut { timeout });
+            }
+            status = process.wait() => {
+                let status = status.wrap_err("Failed to get process status")?;
+                eyre::ensure!(
+                    status.success(),
+                    ServiceError::ProcessExited { status }
+                );
+            }
+        }
+        drop(child_guard);
+
+        ServiceManager::join_loggers(set, pgid, stop_timeout, target).await
+    }
+}
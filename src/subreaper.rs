@@ -1,33 +1,74 @@
 //! Subreaper support for reaping orphaned grandchildren.
+//!
+//! Exited children are found with `waitid(P_ALL, WNOHANG | WNOWAIT)`, which peeks
+//! at them without reaping. Only children which aren't tracked get reaped, so the
+//! exit status of service processes is always left for their owner.
+//!
+//! Peeking keeps returning the same exited child until it is reaped, so the
+//! reaper sleeps while that is a tracked child, until its owner is done with it
+//! and drops its [`ChildGuard`]. Likewise it sleeps while a child is being
+//! spawned, which may not be tracked yet, until the spawn is done. No child is
+//! ever waited on twice, and the reaper never polls.
+//!
+//! Orphans are attributed to the service (or step) whose process group they are
+//! in, as every one of them is spawned as the leader of its own group. Orphans
+//! which left it, e.g. daemons calling `setsid()`, are attributed by the cgroup
+//! they are in instead, if services have their own.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use eyre::{Context, Result};
 #[cfg(target_os = "linux")]
-use log::{debug, warn};
+use log::{debug, info, warn};
 
 #[cfg(target_os = "linux")]
-use std::collections::HashSet;
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::os::fd::{AsFd, FromRawFd, OwnedFd};
 #[cfg(target_os = "linux")]
-use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::path::PathBuf;
+#[cfg(target_os = "linux")]
+use std::sync::{Mutex, OnceLock};
 #[cfg(target_os = "linux")]
 use tokio::signal::unix::{SignalKind, signal};
+#[cfg(target_os = "linux")]
+use tokio::sync::Notify;
 
 #[cfg(target_os = "linux")]
-use nix::sys::wait::{WaitPidFlag, WaitStatus, waitpid};
+use nix::errno::Errno;
 #[cfg(target_os = "linux")]
-use nix::unistd::Pid;
+use nix::sys::signal::killpg;
+#[cfg(target_os = "linux")]
+use nix::sys::wait::{Id, WaitPidFlag, WaitStatus, waitid};
+#[cfg(target_os = "linux")]
+use nix::unistd::{Pid, getpgid};
+
+#[cfg(target_os = "linux")]
+use crate::init::Init;
+#[cfg(target_os = "linux")]
+use crate::process_manager::service_manager::Cgroup;
 
 /// Subreaper configuration and setup.
 pub struct Subreaper;
 
 impl Subreaper {
     /// Enable subreaper mode when supported.
+    ///
+    /// As PID 1 every orphan is reparented to nimi anyway, so only the reaper
+    /// task is needed.
     pub fn enable() -> Result<()> {
         #[cfg(target_os = "linux")]
         {
-            let rc = unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0) };
-            if rc != 0 {
-                return Err(std::io::Error::last_os_error())
-                    .wrap_err("Failed to enable child subreaper");
+            if Init::is_pid1() {
+                info!("Running as PID 1, enabling init mode");
+            } else {
+                let rc = unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0) };
+                if rc != 0 {
+                    return Err(std::io::Error::last_os_error())
+                        .wrap_err("Failed to enable child subreaper");
+                }
             }
             Self::spawn_reaper_task().wrap_err("Failed to spawn subreaper task")?;
         }
@@ -53,27 +94,112 @@ impl Subreaper {
         }
     }
 
-    /// Pause subreaper reaping while a child is spawned and registered.
-    pub fn pause_reaping() -> ReaperPauseGuard {
+    /// Attribute orphans from the process group `pgid` to `orphans`.
+    ///
+    /// Has to be called before the group leader is done spawning, so no orphan
+    /// of the group can be reaped before.
+    pub fn track_group(pgid: Option<u32>, orphans: &Arc<Orphans>) {
+        #[cfg(target_os = "linux")]
+        if let Some(pgid) = pgid {
+            Self::register_group(pgid as i32, Arc::clone(orphans));
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        let _ = (pgid, orphans);
+    }
+
+    /// Attribute orphans in the cgroup at `path` to `orphans`, when they left
+    /// the process group of their service.
+    pub fn track_cgroup(path: &Path, orphans: &Arc<Orphans>) {
+        #[cfg(target_os = "linux")]
+        if let Ok(mut guard) = Self::cgroups().lock() {
+            guard.insert(path.to_owned(), Arc::clone(orphans));
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        let _ = (path, orphans);
+    }
+
+    /// Stop attributing orphans to the cgroup at `path`, once it was removed.
+    pub fn untrack_cgroup(path: &Path) {
+        #[cfg(target_os = "linux")]
+        if let Ok(mut guard) = Self::cgroups().lock() {
+            guard.remove(path);
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        let _ = path;
+    }
+
+    /// Check if nimi has any child processes left, reaped or not.
+    pub fn has_children() -> bool {
         #[cfg(target_os = "linux")]
         {
-            let guard = Self::reaper_guard()
-                .lock()
-                .unwrap_or_else(|err| err.into_inner());
-            ReaperPauseGuard::new(guard)
+            let flags = WaitPidFlag::WEXITED | WaitPidFlag::WNOHANG | WaitPidFlag::WNOWAIT;
+            waitid(Id::All, flags) != Err(Errno::ECHILD)
         }
 
         #[cfg(not(target_os = "linux"))]
         {
-            ReaperPauseGuard::noop()
+            false
         }
     }
+
+    /// Check if a child of nimi is an orphan it adopted, rather than a process
+    /// it spawned itself.
+    pub fn is_orphan(pid: u32) -> bool {
+        #[cfg(target_os = "linux")]
+        {
+            !Self::is_tracked(Pid::from_raw(pid as i32))
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = pid;
+            false
+        }
+    }
+
+    /// Pause subreaper reaping while a child is spawned and registered.
+    ///
+    /// The guard has to be dropped once the child is tracked.
+    pub fn pause_reaping() -> ReaperPauseGuard {
+        #[cfg(target_os = "linux")]
+        Self::spawning().fetch_add(1, Ordering::SeqCst);
+
+        ReaperPauseGuard(())
+    }
+}
+
+/// Orphans reaped from the process groups of a service
+pub struct Orphans {
+    target: Arc<String>,
+    reaped: AtomicUsize,
+}
+
+impl Orphans {
+    /// Create a new counter, logging reaped orphans under `target`
+    pub fn new(target: Arc<String>) -> Arc<Self> {
+        Arc::new(Self {
+            target,
+            reaped: AtomicUsize::new(0),
+        })
+    }
+
+    /// Number of orphans reaped so far
+    pub fn reaped(&self) -> usize {
+        self.reaped.load(Ordering::Relaxed)
+    }
 }
 
 /// Guard to unregister tracked child PIDs when dropped.
+///
+/// Drop it as soon as the owner waited on the child, as orphans which exited
+/// after it can only be reaped once it is unregistered.
 pub struct ChildGuard(Option<i32>);
 
 impl ChildGuard {
+    #[cfg(target_os = "linux")]
     fn new(pid: i32) -> Self {
         Self(Some(pid))
     }
@@ -84,31 +210,14 @@ impl ChildGuard {
 }
 
 /// Guard to pause reaping while spawning and registering children.
-#[cfg(target_os = "linux")]
-pub struct ReaperPauseGuard(Option<MutexGuard<'static, ()>>);
-
-/// Guard to pause reaping while spawning and registering children.
-#[cfg(not(target_os = "linux"))]
 pub struct ReaperPauseGuard(());
 
-#[cfg(target_os = "linux")]
-impl ReaperPauseGuard {
-    fn new(guard: MutexGuard<'static, ()>) -> Self {
-        Self(Some(guard))
-    }
-}
-
-#[cfg(target_os = "linux")]
 impl Drop for ReaperPauseGuard {
     fn drop(&mut self) {
-        let _ = self.0.take();
-    }
-}
-
-#[cfg(not(target_os = "linux"))]
-impl ReaperPauseGuard {
-    fn noop() -> Self {
-        Self(())
+        #[cfg(target_os = "linux")]
+        if Subreaper::spawning().fetch_sub(1, Ordering::SeqCst) == 1 {
+            Subreaper::wake().notify_one();
+        }
     }
 }
 
@@ -117,25 +226,35 @@ impl Drop for ChildGuard {
         #[cfg(target_os = "linux")]
         if let Some(pid) = self.0.take() {
             Subreaper::unregister_child(pid);
+            Subreaper::wake().notify_one();
         }
     }
 }
 
 #[cfg(target_os = "linux")]
 impl Subreaper {
-    fn reaper_guard() -> &'static Mutex<()> {
-        static REAPER_GUARD: OnceLock<Mutex<()>> = OnceLock::new();
-        REAPER_GUARD.get_or_init(|| Mutex::new(()))
+    /// Number of children which are being spawned and not tracked yet
+    fn spawning() -> &'static AtomicUsize {
+        static SPAWNING: AtomicUsize = AtomicUsize::new(0);
+        &SPAWNING
+    }
+
+    /// Wakes the reaper once a spawn is done or a tracked child was unregistered
+    fn wake() -> &'static Notify {
+        static WAKE: OnceLock<Notify> = OnceLock::new();
+        WAKE.get_or_init(Notify::new)
     }
 
-    fn registry() -> &'static Mutex<HashSet<i32>> {
-        static CHILDREN: OnceLock<Mutex<HashSet<i32>>> = OnceLock::new();
-        CHILDREN.get_or_init(|| Mutex::new(HashSet::new()))
+    /// Tracked children with a pidfd referring to them, if the kernel supports it
+    fn registry() -> &'static Mutex<HashMap<i32, Option<OwnedFd>>> {
+        static CHILDREN: OnceLock<Mutex<HashMap<i32, Option<OwnedFd>>>> = OnceLock::new();
+        CHILDREN.get_or_init(|| Mutex::new(HashMap::new()))
     }
 
     fn register_child(pid: i32) {
+        let pidfd = Self::pidfd_open(pid);
         if let Ok(mut guard) = Self::registry().lock() {
-            guard.insert(pid);
+            guard.insert(pid, pidfd);
         }
     }
 
@@ -145,81 +264,168 @@ impl Subreaper {
         }
     }
 
-    fn snapshot_managed() -> HashSet<i32> {
-        Self::registry()
-            .lock()
-            .map(|guard| guard.clone())
-            .unwrap_or_default()
+    /// Process groups of services, by their pgid
+    fn groups() -> &'static Mutex<HashMap<i32, Arc<Orphans>>> {
+        static GROUPS: OnceLock<Mutex<HashMap<i32, Arc<Orphans>>>> = OnceLock::new();
+        GROUPS.get_or_init(|| Mutex::new(HashMap::new()))
     }
 
-    fn collect_children_from_proc() -> Result<HashSet<i32>> {
-        let mut pids = HashSet::new();
-        let task_dir =
-            std::fs::read_dir("/proc/self/task").wrap_err("Failed to read /proc/self/task")?;
+    fn register_group(pgid: i32, orphans: Arc<Orphans>) {
+        if let Ok(mut guard) = Self::groups().lock() {
+            // A pgid can only be reused once its group is gone, so forget those first
+            guard.retain(|&pgid, _| Self::group_exists(pgid));
+            guard.insert(pgid, orphans);
+        }
+    }
 
-        for entry in task_dir {
-            let entry = entry.wrap_err("Failed to read /proc/self/task entry")?;
-            let children_path = entry.path().join("children");
-            let Ok(contents) = std::fs::read_to_string(&children_path) else {
-                continue;
-            };
-            for pid_str in contents.split_whitespace() {
-                if let Ok(pid) = pid_str.parse::<i32>() {
-                    pids.insert(pid);
-                }
+    /// Check if any process, zombies included, is left in a process group
+    fn group_exists(pgid: i32) -> bool {
+        killpg(Pid::from_raw(pgid), None) != Err(Errno::ESRCH)
+    }
+
+    /// Cgroups of services, by their directory
+    fn cgroups() -> &'static Mutex<HashMap<PathBuf, Arc<Orphans>>> {
+        static CGROUPS: OnceLock<Mutex<HashMap<PathBuf, Arc<Orphans>>>> = OnceLock::new();
+        CGROUPS.get_or_init(|| Mutex::new(HashMap::new()))
+    }
+
+    /// Find who an orphan belongs to, by the process group it is in, or else
+    /// by its cgroup
+    ///
+    /// Also returns the process group when the orphan was found by it
+    fn orphans_of(pid: Pid) -> Option<(Option<Pid>, Arc<Orphans>)> {
+        if let Ok(pgid) = getpgid(Some(pid)) {
+            let group = Self::groups()
+                .lock()
+                .ok()
+                .and_then(|guard| guard.get(&pgid.as_raw()).cloned());
+            if let Some(orphans) = group {
+                return Some((Some(pgid), orphans));
             }
         }
 
-        Ok(pids)
+        if Self::cgroups().lock().ok()?.is_empty() {
+            return None;
+        }
+
+        let cgroup = Cgroup::path_of(pid).ok()?;
+        let guard = Self::cgroups().lock().ok()?;
+        guard
+            .get(&cgroup)
+            .map(|orphans| (None, Arc::clone(orphans)))
     }
 
-    fn reap_orphaned_children() {
-        let _guard = Self::reaper_guard()
-            .lock()
-            .unwrap_or_else(|err| err.into_inner());
-        let managed = Self::snapshot_managed();
-        let children = match Self::collect_children_from_proc() {
-            Ok(children) => children,
-            Err(err) => {
-                warn!("Failed to enumerate child processes: {err}");
-                return;
-            }
+    fn pidfd_open(pid: i32) -> Option<OwnedFd> {
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+        if fd < 0 {
+            return None;
+        }
+
+        Some(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+    }
+
+    /// Check if an exited child belongs to a tracked child
+    ///
+    /// Once the owner reaped a tracked child its pid may be reused by an orphan
+    /// before the child is unregistered, so this checks through the pidfd that
+    /// the tracked child hasn't been reaped yet.
+    fn is_tracked(pid: Pid) -> bool {
+        let Ok(guard) = Self::registry().lock() else {
+            return true;
         };
 
-        for pid in children {
-            if managed.contains(&pid) {
-                continue;
+        match guard.get(&pid.as_raw()) {
+            None => false,
+            Some(None) => true,
+            Some(Some(pidfd)) => {
+                let flags = WaitPidFlag::WEXITED | WaitPidFlag::WNOHANG | WaitPidFlag::WNOWAIT;
+                waitid(Id::PIDFd(pidfd.as_fd()), flags) != Err(Errno::ECHILD)
             }
+        }
+    }
 
-            loop {
-                match waitpid(Pid::from_raw(pid), Some(WaitPidFlag::WNOHANG)) {
-                    Ok(WaitStatus::Exited(pid, status)) => {
-                        debug!("Reaped orphaned child {pid} with status {status}");
-                        break;
-                    }
-                    Ok(WaitStatus::Signaled(pid, signal, _)) => {
-                        debug!("Reaped orphaned child {pid} via signal {signal}");
-                        break;
-                    }
-                    Ok(WaitStatus::StillAlive) | Err(nix::errno::Errno::ECHILD) => break,
-                    Ok(_) => continue,
-                    Err(nix::errno::Errno::EINTR) => continue,
-                    Err(err) => {
-                        warn!("Failed to reap child process {pid}: {err}");
-                        break;
-                    }
+    /// Get the pid of the next exited child without reaping it
+    fn peek_exited_child() -> Option<Pid> {
+        let flags = WaitPidFlag::WEXITED | WaitPidFlag::WNOHANG | WaitPidFlag::WNOWAIT;
+
+        loop {
+            match waitid(Id::All, flags) {
+                Ok(status) => return status.pid(),
+                Err(Errno::EINTR) => continue,
+                Err(Errno::ECHILD) => return None,
+                Err(err) => {
+                    warn!("Failed to wait for child processes: {err}");
+                    return None;
                 }
             }
         }
     }
 
+    fn reap(pid: Pid) {
+        // Zombies are still in their process group, so look it up before reaping
+        let owner = Self::orphans_of(pid);
+        let target = owner
+            .as_ref()
+            .map_or(module_path!(), |(_, orphans)| orphans.target.as_str());
+
+        let reaped = loop {
+            match waitid(Id::Pid(pid), WaitPidFlag::WEXITED | WaitPidFlag::WNOHANG) {
+                Ok(WaitStatus::Exited(pid, status)) => {
+                    debug!(target: target, "Reaped orphaned child {pid} with status {status}");
+                    break true;
+                }
+                Ok(WaitStatus::Signaled(pid, signal, _)) => {
+                    debug!(target: target, "Reaped orphaned child {pid} via signal {signal}");
+                    break true;
+                }
+                Ok(_) | Err(Errno::ECHILD) => break false,
+                Err(Errno::EINTR) => continue,
+                Err(err) => {
+                    warn!("Failed to reap child process {pid}: {err}");
+                    break false;
+                }
+            }
+        };
+
+        let Some((pgid, orphans)) = owner.filter(|_| reaped) else {
+            return;
+        };
+        orphans.reaped.fetch_add(1, Ordering::Relaxed);
+
+        // Forget the group once its last process is gone
+        let Some(pgid) = pgid else {
+            return;
+        };
+        if let Ok(mut guard) = Self::groups().lock() {
+            guard.retain(|&group, _| group != pgid.as_raw() || Self::group_exists(group));
+        }
+    }
+
+    /// Reap exited orphans until the next exited child has to be left alone
+    fn reap_orphaned_children() {
+        while let Some(pid) = Self::peek_exited_child() {
+            // A child which is being spawned may not be tracked yet, and a tracked
+            // one is left for its owner. Both wake the reaper once that changed.
+            if Self::spawning().load(Ordering::SeqCst) > 0 || Self::is_tracked(pid) {
+                return;
+            }
+
+            Self::reap(pid);
+        }
+    }
+
     fn spawn_reaper_task() -> Result<()> {
         let mut sigchld =
             signal(SignalKind::child()).wrap_err("Failed to register SIGCHLD handler")?;
         tokio::spawn(async move {
             loop {
-                if sigchld.recv().await.is_none() {
-                    break;
+                tokio::select! {
+                    received = sigchld.recv() => {
+                        if received.is_none() {
+                            break;
+                        }
+                    }
+                    () = Self::wake().notified() => {}
                 }
                 Self::reap_orphaned_children();
             }